@@ -0,0 +1,165 @@
+use std::{fmt::Display, net::SocketAddrV4};
+
+use sdaa_ctrl::ctrl_msg::CtrlMsg;
+
+use crate::{
+    firmware::FirmwareCheck,
+    sdr::{DeviceError, DeviceState, DeviceStep, LockPolicy, SdrCtrl},
+    status::DeviceStatus,
+    time_ref::TimeReference,
+    transport::{CmdReplies, CtrlTransport, RetryPolicy, UdpTransport},
+};
+
+/// Boards of a [`DeviceArray`] that did not get through a step.
+#[derive(Debug, Clone)]
+pub struct ArrayError {
+    pub step: DeviceStep,
+    pub failed: Vec<(SocketAddrV4, DeviceError)>,
+}
+
+impl Display for ArrayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} failed on {} boards", self.step, self.failed.len())?;
+        for (addr, e) in &self.failed {
+            write!(f, "\n  {addr}: {e}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ArrayError {}
+
+impl ArrayError {
+    /// The first board's error, for a single device.
+    pub(crate) fn into_first(self) -> DeviceError {
+        self.failed
+            .into_iter()
+            .next()
+            .map_or(DeviceError::NoReply(self.step), |(_, e)| e)
+    }
+}
+
+/// Status of one board, `None` if it did not answer the query.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardStatus {
+    pub addr: SocketAddrV4,
+    pub status: Option<DeviceStatus>,
+}
+
+impl Display for BoardStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            Some(s) => write!(
+                f,
+                "{}: fw {:#x} {} {} locked={:#x} health={:#x}",
+                self.addr,
+                s.fm_ver,
+                if s.is_awake() { "awake" } else { "off" },
+                if s.is_locked() { "locked" } else { "unlocked" },
+                s.locked.raw,
+                s.health
+            ),
+            None => write!(f, "{}: no reply", self.addr),
+        }
+    }
+}
+
+/// Controls several boards as one through an [`SdrCtrl`] with several
+/// remotes. Every command goes out to all boards in a single exchange, so
+/// a broadcast `Sync` gives them a common `pkt_cnt` epoch, and a step only
+/// succeeds once every board replied normally.
+pub struct DeviceArray<T: CtrlTransport = UdpTransport> {
+    ctrl: SdrCtrl<T>,
+}
+
+impl DeviceArray {
+    pub fn new(boards: Vec<SocketAddrV4>, local_ctrl_addr: SocketAddrV4) -> Self {
+        Self::with_transport(
+            boards,
+            UdpTransport::new(local_ctrl_addr),
+            RetryPolicy::default(),
+        )
+    }
+}
+
+impl<T: CtrlTransport> DeviceArray<T> {
+    pub fn with_transport(boards: Vec<SocketAddrV4>, transport: T, retry: RetryPolicy) -> Self {
+        Self {
+            ctrl: SdrCtrl::with_remotes(boards, transport, retry),
+        }
+    }
+
+    pub fn boards(&self) -> &[SocketAddrV4] {
+        self.ctrl.remotes()
+    }
+
+    /// The control of all boards, for its state and the raw commands.
+    pub fn ctrl(&self) -> &SdrCtrl<T> {
+        &self.ctrl
+    }
+
+    pub fn transport(&self) -> &T {
+        self.ctrl.transport()
+    }
+
+    /// Applied to every board by [`bring_up`](Self::bring_up), ignored by default.
+    pub fn set_firmware_check(&self, check: FirmwareCheck) {
+        self.ctrl.set_firmware_check(check);
+    }
+
+    /// Time reference of the last broadcast sync, shared by all boards.
+    pub fn time_ref(&self) -> Option<TimeReference> {
+        self.ctrl.time_ref()
+    }
+
+    /// Sends `cmd` to every board, repeating it as the retry policy says to
+    /// the boards that did not reply.
+    pub fn send_cmd(&self, cmd: CtrlMsg) -> CmdReplies {
+        self.ctrl.send_cmd(cmd)
+    }
+
+    /// Queries every board.
+    pub fn status(&self) -> Vec<BoardStatus> {
+        self.ctrl.board_status()
+    }
+
+    /// Stops any stream, checks the firmware of every board and derives the
+    /// state of the slowest one.
+    pub fn refresh_state(&self) -> Result<DeviceState, ArrayError> {
+        self.ctrl.refresh_state_all()
+    }
+
+    /// Wakes the boards that are off.
+    pub fn power_up(&self) -> Result<(), ArrayError> {
+        self.ctrl.power_up_all()
+    }
+
+    /// Waits until every board reports lock, see [`LockPolicy`].
+    pub fn lock(&self, policy: &LockPolicy) -> Result<(), ArrayError> {
+        self.ctrl.lock_all(policy)
+    }
+
+    pub fn initialize(&self) -> Result<(), ArrayError> {
+        self.ctrl.initialize_all()
+    }
+
+    /// Broadcasts a single `Sync`, failing unless every board replied to it.
+    /// The epoch is only recorded in that case.
+    pub fn synchronize(&self) -> Result<(), ArrayError> {
+        self.ctrl.synchronize_all()
+    }
+
+    pub fn start_streaming(&self) -> Result<(), ArrayError> {
+        self.ctrl.start_streaming_all()
+    }
+
+    pub fn stop_streaming(&self) -> Result<(), ArrayError> {
+        self.ctrl.stop_streaming_all()
+    }
+
+    /// Wakes, locks, initializes and syncs all boards from whatever state
+    /// they are in, see [`SdrCtrl::bring_up`].
+    pub fn bring_up(&self, policy: &LockPolicy) -> Result<(), ArrayError> {
+        self.ctrl.bring_up_all(policy)
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::{RAW_SAMP_RATE, payload::N_PT_PER_FRAME};
+
+/// Timing of the packet stream as seen by the kernel receive timestamps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArrivalSummary {
+    pub n_pkts: u64,
+    /// Smoothed deviation of inter-arrival times from the nominal frame
+    /// period, in seconds (RFC 3550 style).
+    pub jitter: f64,
+    /// Longest time between two timestamps, in seconds.
+    pub max_gap: f64,
+    /// Number of times packets arrived later than the stall threshold.
+    pub stalls: u64,
+    /// Sample clock rate from a fit of arrival time against `pkt_cnt`.
+    pub samp_rate: Option<f64>,
+}
+
+impl Display for ArrivalSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "jitter={:.3}us max gap={:.3}ms {} stalls",
+            self.jitter * 1e6,
+            self.max_gap * 1e3,
+            self.stalls
+        )?;
+        if let Some(rate) = self.samp_rate {
+            write!(f, " rate={rate:.1}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reduces per-packet receive times to an [`ArrivalSummary`]. Only packets
+/// accepted in sequence should be fed, duplicates and late ones would skew
+/// the fit.
+pub struct ArrivalTracker {
+    stall_threshold: f64,
+    period: f64,
+    first: Option<(u64, DateTime<Utc>)>,
+    last: Option<(f64, f64)>,
+    // running means and co-moments of (pkt_cnt, time) for the rate fit
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    c_xy: f64,
+    summary: ArrivalSummary,
+}
+
+impl ArrivalTracker {
+    pub fn new(stall_threshold: Duration) -> Self {
+        Self {
+            stall_threshold: stall_threshold.as_secs_f64(),
+            period: N_PT_PER_FRAME as f64 / RAW_SAMP_RATE as f64,
+            first: None,
+            last: None,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            m2_x: 0.0,
+            c_xy: 0.0,
+            summary: ArrivalSummary::default(),
+        }
+    }
+
+    /// Starts over, e.g. after `pkt_cnt` restarted.
+    pub fn reset(&mut self) {
+        *self = Self {
+            stall_threshold: self.stall_threshold,
+            ..Self::new(Duration::ZERO)
+        };
+    }
+
+    pub fn observe(&mut self, pkt_cnt: u64, arrival: DateTime<Utc>) {
+        let &mut (cnt0, t0) = self.first.get_or_insert((pkt_cnt, arrival));
+        let x = pkt_cnt as f64 - cnt0 as f64;
+        let y = (arrival - t0).num_nanoseconds().unwrap_or(i64::MAX) as f64 * 1e-9;
+
+        if let Some((lx, ly)) = self.last {
+            let gap = y - ly;
+            let excess = gap - (x - lx) * self.period;
+            self.summary.jitter += (excess.abs() - self.summary.jitter) / 16.0;
+            self.summary.max_gap = self.summary.max_gap.max(gap);
+            if excess > self.stall_threshold {
+                self.summary.stalls += 1;
+            }
+        }
+        self.last = Some((x, y));
+
+        self.summary.n_pkts += 1;
+        let n = self.summary.n_pkts as f64;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / n;
+        self.mean_y += (y - self.mean_y) / n;
+        self.c_xy += dx * (y - self.mean_y);
+        self.m2_x += dx * (x - self.mean_x);
+        if self.m2_x > 0.0 && self.c_xy > 0.0 {
+            self.summary.samp_rate = Some(N_PT_PER_FRAME as f64 * self.m2_x / self.c_xy);
+        }
+    }
+
+    pub fn summary(&self) -> ArrivalSummary {
+        self.summary
+    }
+}
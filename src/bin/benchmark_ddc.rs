@@ -1,18 +1,33 @@
 use clap::Parser;
-
+use sdaa_data::siggen::{Chirp, Pacing, SignalConfig, Tone};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
     #[clap(short = 'r', value_name = "iq rate 240 or 120", default_value_t=240)]
     iq_rate: usize,
+
+    #[clap(long = "noise", value_name = "noise rms", default_value_t = 0.0)]
+    noise_rms: f64,
+
+    #[clap(long = "tone", value_name = "freq:amp")]
+    tones: Vec<Tone>,
+
+    #[clap(long = "chirp", value_name = "f0:f1:sweep:amp")]
+    chirps: Vec<Chirp>,
+
+    #[clap(long = "clip", value_name = "clipping level")]
+    clip: Option<i16>,
+
+    #[clap(short = 'R', long = "real-time")]
+    real_time: bool,
 }
 
 #[cfg(feature = "cuda")]
 fn main() {
     //let (tx,rx)=bounded(256);
     use crossbeam::channel::bounded;
-    use sdaa_data::{ddc::N_PT_PER_FRAME, pipeline::{fake_dev, pkt_ddc, DdcCmd}};
+    use sdaa_data::{ddc::{DownConverter, N_PT_PER_FRAME}, pipeline::{fake_dev, pkt_ddc, DdcCmd}};
 
     let args = Args::parse();
     let (tx_payload, rx_payload)=bounded(1024);
@@ -24,15 +39,30 @@ fn main() {
         .send(DdcCmd::LoCh(N_PT_PER_FRAME as isize/4))
         .expect("failed to send loch");
 
-    std::thread::spawn(move || fake_dev(tx_payload, rx_recv_cmd));
+    let signal = SignalConfig {
+        noise_rms: args.noise_rms,
+        tones: args.tones,
+        chirps: args.chirps,
+        clip: args.clip,
+    };
+    let pacing = if args.real_time {
+        Pacing::RealTime
+    } else {
+        Pacing::FreeRunning
+    };
+    let ndec=480/args.iq_rate;
+    let fir_coeffs = match args.iq_rate {
+        240 => sdaa_data::ddc::fir_coeffs_full(),
+        120 => sdaa_data::ddc::fir_coeffs_half(),
+        _ => panic!("invalid iq rate"),
+    };
+    let ddc = DownConverter::new(ndec, &fir_coeffs).expect("failed to set up ddc");
+    std::thread::spawn(move || fake_dev(tx_payload, rx_recv_cmd, signal, pacing));
     std::thread::spawn(move || {
-        let ndec=480/args.iq_rate;
-        let fir_coeffs = match args.iq_rate {
-            240 => sdaa_data::ddc::fir_coeffs_full(),
-            120 => sdaa_data::ddc::fir_coeffs_half(),
-            _ => panic!("invalid iq rate"),
-        };
-        pkt_ddc(rx_payload, tx_ddc, ndec, rx_ddc_cmd, tx_recv_cmd, &fir_coeffs)});
+        if let Err(e) = pkt_ddc(rx_payload, tx_ddc, ddc, rx_ddc_cmd, tx_recv_cmd) {
+            eprintln!("ddc stopped: {e}");
+        }
+    });
 
     for _i in 0.. {
         let _ddc = rx_ddc.recv().expect("failed to recv ddc payload");
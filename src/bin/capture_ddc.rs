@@ -1,9 +1,25 @@
-use std::{fs::File, io::Write};
+use std::{
+    fs::File,
+    io::Write,
+    net::{Ipv4Addr, SocketAddrV4},
+    time::Duration,
+};
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use num::Complex;
 
-use sdaa_data::{sdr::{Sdr, SdrSmpRate}, utils::slice_as_u8};
+use sdaa_data::{
+    firmware::FirmwareCheck,
+    meta::CaptureMeta,
+    payload::N_PT_PER_FRAME,
+    pipeline::GroupSource,
+    recovery::RecoveryPolicy,
+    sdr::{LockPolicy, SdrBuilder, SdrSmpRate},
+    time_ref::{ARRIVAL_LATENCY, TimeReference},
+    utils::{iface_addr, slice_as_u8},
+    window::{CaptureWindow, Clip, GapReport},
+};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -11,8 +27,36 @@ struct Args {
     #[clap(short = 'a', value_name = "local payload ip:port")]
     local_payload_addr: String,
 
-    #[clap(short = 'A', value_name = "remote ctrl ip:port")]
-    remote_ctrl_addr: String,
+    #[clap(
+        short = 'm',
+        long = "maddr",
+        value_name = "group[@source]",
+        help = "multicast group to join, may be given several times, all carrying the same board"
+    )]
+    multicast_addrs: Vec<GroupSource>,
+
+    #[clap(
+        short = 'i',
+        long = "iface",
+        value_name = "interface name or ip to join on, the ip of -a by default"
+    )]
+    iface: Option<String>,
+
+    #[clap(
+        long = "source",
+        value_name = "only accept the groups without an @source from this sender ip"
+    )]
+    source: Option<Ipv4Addr>,
+
+    #[clap(short = 'A', value_name = "remote ctrl ip:port", required_unless_present = "listen")]
+    remote_ctrl_addr: Option<String>,
+
+    #[clap(
+        long = "listen",
+        conflicts_with_all = ["remote_ctrl_addr", "discipline_period", "health_period", "recover"],
+        help = "only receive, e.g. a multicast stream another host controls"
+    )]
+    listen: bool,
 
     #[clap(
         short = 'L',
@@ -35,6 +79,41 @@ struct Args {
 
     #[clap(short = 'C')]
     ignore_locking: bool,
+
+    #[clap(long = "start", value_name = "UTC start time, e.g. 2025-01-01T00:00:00Z")]
+    start: Option<DateTime<Utc>>,
+
+    #[clap(long = "duration", value_name = "seconds", requires = "start")]
+    duration: Option<f64>,
+
+    #[clap(long = "from-pkt", value_name = "first pkt_cnt", conflicts_with = "start")]
+    from_pkt: Option<u64>,
+
+    #[clap(long = "to-pkt", value_name = "pkt_cnt to stop before", conflicts_with = "start")]
+    to_pkt: Option<u64>,
+
+    #[clap(short = 'D', long = "discipline", value_name = "tick query period in seconds")]
+    discipline_period: Option<f64>,
+
+    #[clap(short = 'H', long = "health", value_name = "health query period in seconds")]
+    health_period: Option<f64>,
+
+    #[clap(long = "recover", help = "bring the device back up after lock loss")]
+    recover: bool,
+
+    #[clap(
+        long = "recover-on-restart",
+        requires = "recover",
+        help = "also bring the device back up when the stream restarts"
+    )]
+    recover_on_restart: bool,
+
+    #[clap(
+        long = "firmware-check",
+        value_name = "ignore, warn or refuse unsupported firmware",
+        default_value = "ignore"
+    )]
+    firmware_check: FirmwareCheck,
 }
 
 #[cfg(feature = "cuda")]
@@ -45,44 +124,185 @@ fn main() {
 
     let args = Args::parse();
 
-    let (sdr, rx_ddc, tx_cmd) = Sdr::new(
-        args.remote_ctrl_addr
-            .parse()
-            .expect("failed to parse remote ctrl addr"),
-        args.local_ctrl_addr
-            .parse()
-            .expect("failed to parse local ctrl addr"),
-        args.local_payload_addr
-            .parse()
-            .expect("failed to parse local payload addr"),
-        SdrSmpRate::from_ndec(480/args.iq_rate),
-    );
-
     let mut dump_file = args
         .outname
-        .map(|outname| File::create(&outname).expect("failed to create dump file"));
+        .as_ref()
+        .map(|outname| File::create(outname).expect("failed to create dump file"));
     let mut _bytes_written = 0;
-    tx_cmd
-        .send(sdaa_data::pipeline::DdcCmd::LoCh(args.lo_ch))
-        .expect("failed to send cmd");
-    sdr.ctrl.wakeup();
-    if !args.ignore_locking {
-        sdr.ctrl.wait_until_locked(60);
+    let lock_policy = if args.ignore_locking {
+        LockPolicy {
+            settle: Duration::ZERO,
+            timeout: Duration::ZERO,
+            ignore_locking: true,
+            ..Default::default()
+        }
     } else {
-        eprintln!("ignoring clock locking");
+        LockPolicy::default()
+    };
+    let payload_addr: SocketAddrV4 = args
+        .local_payload_addr
+        .parse()
+        .expect("failed to parse local payload addr");
+    // multicast groups are received on the port of -a
+    let bind_addr = if args.multicast_addrs.is_empty() {
+        payload_addr
+    } else {
+        SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, payload_addr.port())
+    };
+    let mut builder = match &args.remote_ctrl_addr {
+        Some(remote_ctrl_addr) => SdrBuilder::new(
+            remote_ctrl_addr
+                .parse()
+                .expect("failed to parse remote ctrl addr"),
+            args.local_ctrl_addr
+                .parse()
+                .expect("failed to parse local ctrl addr"),
+            bind_addr,
+        ),
+        // nothing is sent, so a second host cannot disturb the device
+        None => SdrBuilder::listen_only(bind_addr),
+    };
+    let iface = match &args.iface {
+        Some(iface) => iface_addr(iface).expect("failed to find interface"),
+        None => *payload_addr.ip(),
+    };
+    for group in &args.multicast_addrs {
+        builder = builder.membership(group.membership(iface, args.source));
+    }
+    let (mut sdr, rx_ddc, tx_cmd) = builder
+        .smp_rate(SdrSmpRate::from_ndec(480 / args.iq_rate).expect("invalid iq rate"))
+        .lo_ch(args.lo_ch)
+        .firmware_check(args.firmware_check)
+        .bring_up(lock_policy)
+        .build()
+        .expect("failed to set up sdr");
+    if let Some(period) = args.discipline_period {
+        sdr.start_clock_discipline(Duration::from_secs_f64(period))
+            .expect("failed to start clock discipline");
+    }
+    if let Some(period) = args.health_period {
+        let rx_event = sdr
+            .start_health_monitor(Duration::from_secs_f64(period))
+            .expect("failed to start health monitor");
+        std::thread::spawn(move || {
+            for event in rx_event {
+                eprintln!("device: {event}");
+            }
+        });
+    }
+    if args.recover {
+        sdr.start_recovery(RecoveryPolicy {
+            on_restart: args.recover_on_restart,
+            lock: lock_policy,
+            ..Default::default()
+        })
+        .expect("failed to start recovery");
+    }
+    if let Some(ctrl) = &sdr.ctrl {
+        ctrl.start_streaming().expect("failed to start stream");
     }
 
-    sdr.ctrl.init();
-    sdr.ctrl.sync();
-    sdr.ctrl.stream_start();
+    // breaks are counted in output samples written before them, each with
+    // the epoch of the segment that follows
+    let save_meta = |time_ref, first_sample, breaks: &[(usize, TimeReference)]| {
+        if let Some(ref outname) = args.outname {
+            let mut meta = CaptureMeta::new(time_ref, first_sample)
+                .with_firmware(sdr.ctrl.as_ref().and_then(|c| c.firmware_version()))
+                .with_payload_version(sdr.payload_version())
+                .with("ndec", 480 / args.iq_rate)
+                .with("lo_ch", args.lo_ch);
+            for (at, tref) in breaks {
+                meta = meta
+                    .with("break_at", at)
+                    .with("break_epoch_utc", tref.epoch.to_rfc3339());
+            }
+            meta.save(outname).expect("failed to write meta file");
+        }
+    };
+    // a sync since the last segment started dates the new one, otherwise
+    // only the arrival of its first block does
+    let derive_segment = |first_sample: u64, synced: Option<DateTime<Utc>>| {
+        let time_ref = sdr
+            .time_ref()
+            .filter(|_| sdr.ctrl.as_ref().and_then(|c| c.time_ref()).map(|t| t.epoch) != synced)
+            .unwrap_or_else(|| {
+                TimeReference::from_arrival(
+                    first_sample / N_PT_PER_FRAME as u64,
+                    Utc::now(),
+                    ARRIVAL_LATENCY,
+                )
+            });
+        let window = if args.from_pkt.is_some() || args.to_pkt.is_some() {
+            let pkts = args.from_pkt.unwrap_or(0)..args.to_pkt.unwrap_or(u64::MAX);
+            println!("waiting for pkt_cnt {pkts:?}");
+            CaptureWindow::from_pkt_range(pkts)
+        } else {
+            args.start.map_or_else(CaptureWindow::default, |start| {
+                println!("waiting for {start}");
+                CaptureWindow::from_utc(
+                    &time_ref,
+                    start,
+                    args.duration.map(Duration::from_secs_f64),
+                )
+            })
+        };
+        (time_ref, window)
+    };
     let mut nsamp: Option<usize> = args.nsamp.map(|x| x * 1_000_000);
-    for _i in 0.. {
+    let mut segment = None;
+    let mut synced = None;
+    let mut report = None;
+    let mut first_written = None;
+    let mut n_written = 0;
+    let mut breaks = Vec::new();
+    loop {
         let ddc = rx_ddc.recv().expect("failed to recv ddc payload");
 
+        let mut broke = false;
+        if ddc.discontinuity && segment.is_some() {
+            println!("stream broke after {n_written} samples");
+            // the device started counting over, so the old epoch is meaningless
+            segment = None;
+            broke = first_written.is_some();
+        }
+        let (time_ref, window) = *segment.get_or_insert_with(|| {
+            let segment = derive_segment(ddc.first_sample, synced);
+            synced = sdr.ctrl.as_ref().and_then(|c| c.time_ref()).map(|t| t.epoch);
+            segment
+        });
+        let report = report.get_or_insert_with(|| GapReport::new(&window));
+        if ddc.discontinuity {
+            report.mark_break();
+        }
+        if broke {
+            breaks.push((n_written, time_ref));
+            if let Some((first_sample, tref)) = first_written {
+                save_meta(tref, first_sample, &breaks);
+            }
+        }
+        let range = match window.clip(ddc.first_sample, ddc.n_total, ddc.len()) {
+            Clip::Before => continue,
+            Clip::Inside(range) => range,
+            Clip::After => break,
+        };
+
+        if first_written.is_none() {
+            let first_sample = ddc.first_sample + (range.start * ddc.n_total / ddc.len()) as u64;
+            save_meta(time_ref, first_sample, &breaks);
+            first_written = Some((first_sample, time_ref));
+        }
+        let raw_per_item = ddc.n_total / ddc.len();
+        report.record(
+            ddc.first_sample + (range.start * raw_per_item) as u64,
+            range.len() * raw_per_item,
+            ddc.n_valid * range.len() / ddc.len(),
+        );
+        let data = &ddc[range];
+
         let n_to_write = if let Some(n) = nsamp {
-            n.min(ddc.len())
+            n.min(data.len())
         } else {
-            ddc.len()
+            data.len()
         };
 
         if n_to_write == 0 {
@@ -92,13 +312,38 @@ fn main() {
         nsamp.iter_mut().for_each(|x| {
             *x -= n_to_write;
         });
+        n_written += n_to_write;
         if let Some(ref mut f) = dump_file {
             //dump_file = Some(File::create(outname).unwrap());
-            f.write_all(slice_as_u8(&ddc[..n_to_write]))
+            f.write_all(slice_as_u8(&data[..n_to_write]))
                 .expect("failed to write");
-            _bytes_written += ddc.len() * std::mem::size_of::<Complex<f32>>();
+            _bytes_written += n_to_write * std::mem::size_of::<Complex<f32>>();
             //println!("{} MBytes written", bytes_written as f64 / 1e6);
         }
+
+        if window.is_complete(ddc.first_sample + ddc.n_total as u64) {
+            break;
+        }
+    }
+    if let (Some((_, window)), Some(mut report)) = (segment, report) {
+        report.finish(&window);
+        println!("{report}");
+    }
+    // the clock fit improves over the capture, so tag the file with the final
+    // one, unless the stream broke since the file started
+    if let Some((first_sample, time_ref)) = first_written {
+        let synced = sdr.ctrl.as_ref().and_then(|c| c.time_ref());
+        let time_ref = match (sdr.clock_fit(), synced) {
+            (Some(fit), Some(synced)) if breaks.is_empty() => {
+                println!("clock drift {:e} from {} queries", fit.drift, fit.n_points);
+                if !fit.agrees_with(&synced) {
+                    println!("clock fit disagrees with the sync, not applied");
+                }
+                fit.correct(&synced)
+            }
+            _ => time_ref,
+        };
+        save_meta(time_ref, first_sample, &breaks);
     }
     tx_cmd
         .send(DdcCmd::Destroy)
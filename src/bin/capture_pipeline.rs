@@ -1,11 +1,12 @@
 use lockfree_object_pool::LinearOwnedReusable;
-use std::{fs::File, io::Write, net::UdpSocket};
+use std::{fs::File, io::Write, net::UdpSocket, sync::Arc};
 
 use clap::Parser;
 use crossbeam::channel::unbounded;
 use sdaa_data::{
     payload::Payload,
-    pipeline::recv_pkt,
+    pipeline::{RecvConfig, recv_pkt},
+    stats::RecvStats,
     utils::{as_u8_slice, set_recv_buffer_size},
 };
 
@@ -48,7 +49,13 @@ fn main() {
     let (tx, rx) = unbounded::<LinearOwnedReusable<Payload>>();
     let (_tx_cmd, rx_cmd) = unbounded();
     //let pool1 = Arc::clone(&pool);
-    std::thread::spawn(|| recv_pkt(socket.into(), tx, rx_cmd));
+    let stats = Arc::new(RecvStats::default());
+    let stats1 = Arc::clone(&stats);
+    std::thread::spawn(|| {
+        if let Err(e) = recv_pkt(socket.into(), RecvConfig::default(), stats1, tx, rx_cmd) {
+            eprintln!("receiver stopped: {e}");
+        }
+    });
 
     let mut npkt_to_dump = 0;
     let mut dump_file = None;
@@ -65,7 +72,12 @@ fn main() {
         let payload = rx.recv().expect("failed to recv payload");
 
         if payload.pkt_cnt % 100000 == 0 {
-            println!("cnt: {} queue cnt: {}", payload.pkt_cnt, rx.len());
+            println!(
+                "cnt: {} queue cnt: {} {}",
+                payload.pkt_cnt,
+                rx.len(),
+                stats.snapshot()
+            );
         }
 
         if let Some(c) = old_cnt
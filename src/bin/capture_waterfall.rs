@@ -2,15 +2,23 @@ use lockfree_object_pool::LinearOwnedReusable;
 use std::{
     io::Write,
     net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    sync::Arc,
+    time::Duration,
 };
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use crossbeam::channel::bounded;
 use sdaa_data::{
     RAW_SAMP_RATE,
-    payload::Payload,
-    pipeline::{MaybeMulticastReceiver, RecvCmd, pkt_wf, recv_pkt},
-    utils::slice_as_u8,
+    payload::{N_PT_PER_FRAME, Payload},
+    fill::GapFill,
+    meta::CaptureMeta,
+    pipeline::{Block, GroupSource, MaybeMulticastReceiver, Membership, RecvCmd, RecvConfig, pkt_wf, recv_pkt},
+    stats::RecvStats,
+    time_ref::{ARRIVAL_LATENCY, TimeReference},
+    utils::{iface_addr, slice_as_u8},
+    window::{CaptureWindow, Clip},
 };
 
 #[derive(Parser, Debug)]
@@ -19,8 +27,26 @@ struct Args {
     #[clap(short = 'a', long = "addr", value_name = "ip:port")]
     local_addr: String,
 
-    #[clap(short = 'm', long = "maddr", value_name = "ip")]
-    multicast_addr: Option<String>,
+    #[clap(
+        short = 'm',
+        long = "maddr",
+        value_name = "group[@source]",
+        help = "multicast group to join, may be given several times, all carrying the same board"
+    )]
+    multicast_addrs: Vec<GroupSource>,
+
+    #[clap(
+        short = 'i',
+        long = "iface",
+        value_name = "interface name or ip to join on, the ip of -a by default"
+    )]
+    iface: Option<String>,
+
+    #[clap(
+        long = "source",
+        value_name = "only accept the groups without an @source from this sender ip"
+    )]
+    source: Option<Ipv4Addr>,
 
     #[clap(short = 'o', long = "out", value_name = "out name")]
     outname: Option<String>,
@@ -43,6 +69,20 @@ struct Args {
         default_value_t = 1024
     )]
     nint: usize,
+
+    #[clap(
+        short = 'f',
+        long = "fill",
+        value_name = "zeros, noise, hold or none",
+        default_value = "zeros"
+    )]
+    gap_fill: GapFill,
+
+    #[clap(long = "start", value_name = "UTC start time, e.g. 2025-01-01T00:00:00Z")]
+    start: Option<DateTime<Utc>>,
+
+    #[clap(long = "duration", value_name = "seconds", requires = "start")]
+    duration: Option<f64>,
 }
 
 fn main() {
@@ -52,14 +92,20 @@ fn main() {
     let nbatch = if args.nbatch == 0 { nint } else { args.nbatch };
     let addr = args.local_addr.parse::<SocketAddrV4>().unwrap();
 
-    let socket = if let Some(mcast_addr_str) = args.multicast_addr {
-        let local_iface = addr.ip().clone(); // 改成你网卡的实际 IPv4 地址
-        let mcast_addr = mcast_addr_str.parse::<Ipv4Addr>().unwrap();
-        assert!(mcast_addr.is_multicast());
-
-        MaybeMulticastReceiver::new(
+    let socket = if !args.multicast_addrs.is_empty() {
+        let local_iface = match &args.iface {
+            Some(iface) => iface_addr(iface).expect("failed to find interface"),
+            None => *addr.ip(),
+        };
+        let memberships: Vec<Membership> = args
+            .multicast_addrs
+            .iter()
+            .map(|group| group.membership(local_iface, args.source))
+            .collect();
+
+        MaybeMulticastReceiver::with_memberships(
             SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), addr.port()),
-            Some((mcast_addr, local_iface)),
+            &memberships,
         )
         .unwrap()
     } else {
@@ -67,7 +113,7 @@ fn main() {
     };
 
     let (tx_payload, rx_payload) = bounded::<LinearOwnedReusable<Payload>>(16384);
-    let (tx_wf, rx_wf) = bounded::<LinearOwnedReusable<Vec<f32>>>(4096);
+    let (tx_wf, rx_wf) = bounded::<LinearOwnedReusable<Block<f32>>>(4096);
     let (tx_recv_cmd, rx_recv_cmd) = bounded(1024);
 
     ctrlc::set_handler(move || {
@@ -77,9 +123,23 @@ fn main() {
     .expect("Error setting Ctrl+C handler");
 
     //let pool1 = Arc::clone(&pool);
-    std::thread::spawn(move || pkt_wf(rx_payload, tx_wf, args.nch, nbatch, nint));
+    std::thread::spawn(move || {
+        if let Err(e) = pkt_wf(rx_payload, tx_wf, args.nch, nbatch, nint) {
+            eprintln!("waterfall stopped: {e}");
+        }
+    });
     //std::thread::sleep(std::time::Duration::from_secs(1));
-    std::thread::spawn(|| recv_pkt(socket, tx_payload, rx_recv_cmd));
+    let stats = Arc::new(RecvStats::default());
+    let stats1 = Arc::clone(&stats);
+    let recv_config = RecvConfig {
+        gap_fill: args.gap_fill,
+        ..Default::default()
+    };
+    std::thread::spawn(|| {
+        if let Err(e) = recv_pkt(socket, recv_config, stats1, tx_payload, rx_recv_cmd) {
+            eprintln!("receiver stopped: {e}");
+        }
+    });
     let dt = (args.nch * 2 * args.nint) as f64 / RAW_SAMP_RATE as f64;
 
     //let mut dump_file = None;
@@ -94,11 +154,50 @@ fn main() {
         RAW_SAMP_RATE,
         dt_per_iter
     );
+    let mut window = None;
+    let mut started = false;
     for _i in 0.. {
         if let Ok(x) = rx_wf.recv() {
+            let (time_ref, window) = *window.get_or_insert_with(|| {
+                let time_ref = TimeReference::from_arrival(
+                    x.first_sample / N_PT_PER_FRAME as u64,
+                    Utc::now(),
+                    ARRIVAL_LATENCY,
+                );
+                let window = args.start.map_or_else(CaptureWindow::default, |start| {
+                    println!("waiting for {start}");
+                    CaptureWindow::from_utc(
+                        &time_ref,
+                        start,
+                        args.duration.map(Duration::from_secs_f64),
+                    )
+                });
+                (time_ref, window)
+            });
+            let nrow = x.len() / args.nch;
+            let rows = match window.clip(x.first_sample, x.n_total, nrow) {
+                Clip::Before => continue,
+                Clip::Inside(rows) => rows,
+                Clip::After => break,
+            };
+            if !started {
+                started = true;
+                if let Some(ref outname) = args.outname {
+                    let first_sample = x.first_sample + (rows.start * x.n_total / nrow) as u64;
+                    // no control connection, so only the packets tell the version
+                    CaptureMeta::new(time_ref, first_sample)
+                        .with_firmware(None)
+                        .with_payload_version(stats.payload_version())
+                        .with("nch", args.nch)
+                        .with("nint", nint)
+                        .with("dt", dt)
+                        .save(outname)
+                        .expect("failed to write meta file");
+                }
+            }
             time_elapsed += dt_per_iter;
             if time_elapsed as usize != old_time_elapsed_integer {
-                println!("{time_elapsed}");
+                println!("{time_elapsed} {}", stats.snapshot());
                 old_time_elapsed_integer = time_elapsed as usize;
             }
 
@@ -113,7 +212,8 @@ fn main() {
                 })
                 .iter_mut()
                 .for_each(|f| {
-                    f.write_all(slice_as_u8(&x[..])).unwrap();
+                    f.write_all(slice_as_u8(&x[rows.start * args.nch..rows.end * args.nch]))
+                        .unwrap();
                 });
         }else{
             break;
@@ -3,14 +3,28 @@ use std::{
     fs::File,
     io::{BufWriter, Write},
     net::UdpSocket,
+    sync::Arc,
+    time::Duration,
 };
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
-use crossbeam::channel::unbounded;
+use crossbeam::channel::{Receiver, Sender, unbounded};
 use sdaa_data::{
-    payload::Payload,
-    pipeline::recv_pkt,
-    utils::{as_u8_slice, set_recv_buffer_size},
+    fill::GapFill,
+    meta::CaptureMeta,
+    payload::{N_PT_PER_FRAME, Payload},
+    pipeline::{RecvCmd, RecvConfig, recv_pkt},
+    stats::RecvStats,
+    time_ref::{ARRIVAL_LATENCY, TimeReference},
+    utils::{set_recv_buffer_size, slice_as_u8},
+    window::{CaptureWindow, Clip, GapReport},
+};
+
+#[cfg(feature = "io_uring")]
+use sdaa_data::{
+    pipeline::recv_pkt_from,
+    uring::{UringFileWriter, UringReceiver},
 };
 
 #[derive(Parser, Debug)]
@@ -30,6 +44,101 @@ struct Args {
 
     #[clap(short = 'b', value_name = "buffer size in MB")]
     buffer_size_mega_byte: Option<usize>,
+
+    #[clap(
+        short = 'f',
+        long = "fill",
+        value_name = "zeros, noise, hold or none",
+        default_value = "zeros"
+    )]
+    gap_fill: GapFill,
+
+    #[clap(long = "start", value_name = "UTC start time, e.g. 2025-01-01T00:00:00Z")]
+    start: Option<DateTime<Utc>>,
+
+    #[clap(long = "duration", value_name = "seconds", requires = "start")]
+    duration: Option<f64>,
+
+    #[clap(long = "from-pkt", value_name = "first pkt_cnt", conflicts_with = "start")]
+    from_pkt: Option<u64>,
+
+    #[clap(long = "to-pkt", value_name = "pkt_cnt to stop before", conflicts_with = "start")]
+    to_pkt: Option<u64>,
+
+    #[clap(short = 'T', long = "timestamps")]
+    timestamps: bool,
+
+    #[cfg(feature = "io_uring")]
+    #[clap(short = 'U', long = "uring")]
+    use_uring: bool,
+}
+
+impl Args {
+    #[cfg(feature = "io_uring")]
+    fn use_uring(&self) -> bool {
+        self.use_uring
+    }
+
+    #[cfg(not(feature = "io_uring"))]
+    fn use_uring(&self) -> bool {
+        false
+    }
+}
+
+fn spawn_receiver(
+    socket: UdpSocket,
+    config: RecvConfig,
+    stats: Arc<RecvStats>,
+    tx: Sender<LinearOwnedReusable<Payload>>,
+    rx_cmd: Receiver<RecvCmd>,
+    use_uring: bool,
+) {
+    #[cfg(feature = "io_uring")]
+    if use_uring {
+        let source = UringReceiver::new(socket.into(), 4096).expect("failed to set up io_uring");
+        std::thread::spawn(|| recv_pkt_from(source, config, stats, tx, rx_cmd));
+        return;
+    }
+    assert!(!use_uring);
+    std::thread::spawn(|| {
+        if let Err(e) = recv_pkt(socket.into(), config, stats, tx, rx_cmd) {
+            eprintln!("receiver stopped: {e}");
+        }
+    });
+}
+
+fn create_output(fname: &str, buffer_size: usize, use_uring: bool) -> Box<dyn Write> {
+    #[cfg(feature = "io_uring")]
+    if use_uring {
+        // O_DIRECT needs the chunk size to be a multiple of the block size
+        return Box::new(
+            UringFileWriter::create(fname, buffer_size, 4).expect("failed to create output file"),
+        );
+    }
+    assert!(!use_uring);
+    Box::new(BufWriter::with_capacity(
+        buffer_size,
+        File::create(fname).expect("failed to create output file"),
+    ))
+}
+
+/// Breaks are counted in samples written to the file before them, each
+/// with the epoch of the segment that follows.
+fn save_meta(
+    path: &str,
+    time_ref: TimeReference,
+    first_sample: u64,
+    payload_version: u32,
+    breaks: &[(u64, TimeReference)],
+) {
+    // no control connection, so only the packets tell which firmware this is
+    let mut meta = CaptureMeta::new(time_ref, first_sample).with_payload_version(Some(payload_version));
+    for (at, tref) in breaks {
+        meta = meta
+            .with("break_at", at)
+            .with("break_epoch_utc", tref.epoch.to_rfc3339());
+    }
+    meta.save(path).expect("failed to write meta file");
 }
 
 fn main() {
@@ -43,31 +152,131 @@ fn main() {
     let (tx, rx) = unbounded::<LinearOwnedReusable<Payload>>();
     let (_tx_cmd, rx_cmd) = unbounded();
     //let pool1 = Arc::clone(&pool);
-    std::thread::spawn(|| recv_pkt(socket.into(), tx, rx_cmd));
+    let stats = Arc::new(RecvStats::default());
+    let recv_config = RecvConfig {
+        gap_fill: args.gap_fill,
+        timestamps: args.timestamps,
+        ..Default::default()
+    };
+    spawn_receiver(
+        socket,
+        recv_config,
+        Arc::clone(&stats),
+        tx,
+        rx_cmd,
+        args.use_uring(),
+    );
 
     let mut npkts_received = 0;
     let mut current_file_no = 0;
     let mut current_file_pkts = 0;
+    let mut time_ref = None;
+    let mut window = None;
+    let mut report: Option<GapReport> = None;
+    let mut file_start = None;
+    let mut file_samples = 0;
+    let mut file_breaks = Vec::new();
 
-    let mut dump_file = if let Some(ref fname) = args.outname {
-        Some(BufWriter::with_capacity(
-            buffer_size_mega_byte * 1024 * 1024,
-            if args.npkts_per_file.is_some() {
-                File::create(format!("{fname}{current_file_no}.bin"))
-                    .expect("failed to create output file")
-            } else {
-                File::create(fname).expect("failed to create output file")
-            },
-        ))
-    } else {
-        None
+    let file_name = |fname: &String, file_no: usize| {
+        if args.npkts_per_file.is_some() {
+            format!("{fname}{file_no}.bin")
+        } else {
+            fname.clone()
+        }
     };
 
+    let mut dump_file = args.outname.as_ref().map(|fname| {
+        create_output(
+            &file_name(fname, current_file_no),
+            buffer_size_mega_byte * 1024 * 1024,
+            args.use_uring(),
+        )
+    });
+
     loop {
         let payload = rx.recv().expect("failed to recv payload");
 
+        let mut broke = false;
+        if payload.is_discontinuity() {
+            println!("stream broke at pkt_cnt {}", payload.pkt_cnt);
+            // pkt_cnt started over, so the epoch and with it the window has
+            // to be guessed again
+            time_ref = None;
+            window = None;
+            if let Some(report) = report.as_mut() {
+                report.mark_break();
+            }
+            broke = current_file_pkts > 0;
+        }
+
+        // no control connection here, so the epoch is only known from arrival
+        let time_ref = *time_ref.get_or_insert_with(|| {
+            TimeReference::from_arrival(payload.pkt_cnt, Utc::now(), ARRIVAL_LATENCY)
+        });
+        if broke
+            && let Some(ref fname) = args.outname
+            && let Some((tref, first_sample)) = file_start
+        {
+            file_breaks.push((file_samples, time_ref));
+            save_meta(
+                &file_name(fname, current_file_no),
+                tref,
+                first_sample,
+                payload.version,
+                &file_breaks,
+            );
+        }
+        let window = window.get_or_insert_with(|| {
+            if args.from_pkt.is_some() || args.to_pkt.is_some() {
+                let pkts = args.from_pkt.unwrap_or(0)..args.to_pkt.unwrap_or(u64::MAX);
+                println!("waiting for pkt_cnt {pkts:?}");
+                return CaptureWindow::from_pkt_range(pkts);
+            }
+            args.start.map_or_else(CaptureWindow::default, |start| {
+                println!("waiting for {start}");
+                CaptureWindow::from_utc(&time_ref, start, args.duration.map(Duration::from_secs_f64))
+            })
+        });
+        let report = report.get_or_insert_with(|| GapReport::new(window));
+        let first_sample = payload.pkt_cnt * N_PT_PER_FRAME as u64;
+        let range = match window.clip(first_sample, N_PT_PER_FRAME, N_PT_PER_FRAME) {
+            Clip::Before => continue,
+            Clip::Inside(range) => range,
+            Clip::After => break,
+        };
+        let n = range.len();
+        report.record(
+            first_sample + range.start as u64,
+            n,
+            if payload.is_gap_filled() { 0 } else { n },
+        );
+
+        if current_file_pkts == 0
+            && let Some(ref fname) = args.outname
+        {
+            let start = (time_ref, first_sample + range.start as u64);
+            save_meta(
+                &file_name(fname, current_file_no),
+                start.0,
+                start.1,
+                payload.version,
+                &[],
+            );
+            file_start = Some(start);
+            file_samples = 0;
+            file_breaks.clear();
+        }
+
         if payload.pkt_cnt % 100000 == 0 {
-            println!("cnt: {} queue cnt: {}", payload.pkt_cnt, rx.len());
+            println!(
+                "cnt: {} queue cnt: {} {}",
+                payload.pkt_cnt,
+                rx.len(),
+                stats.snapshot()
+            );
+            if let Some(arrival) = stats.arrival() {
+                println!("arrival: {arrival}");
+            }
         }
 
         // dump_file.as_mut().map(|f| {
@@ -75,13 +284,18 @@ fn main() {
         // });
 
         if let Some(f) = dump_file.as_mut() {
-            f.write_all(as_u8_slice(&payload.data))
+            f.write_all(slice_as_u8(&payload.data[range]))
                 .expect("failed to write to dump file");
+            file_samples += n as u64;
         }
 
         npkts_received += 1;
         current_file_pkts += 1;
 
+        if window.is_complete(first_sample + N_PT_PER_FRAME as u64) {
+            break;
+        }
+
         if let Some(n) = args.npkts_to_recv
             && npkts_received >= n
         {
@@ -94,12 +308,17 @@ fn main() {
         {
             current_file_no += 1;
             current_file_pkts = 0;
-            dump_file = Some(BufWriter::with_capacity(
+            dump_file = Some(create_output(
+                &file_name(fname, current_file_no),
                 buffer_size_mega_byte * 1024 * 1024,
-                File::create(format!("{fname}{current_file_no}.bin"))
-                    .expect("failed to create output file"),
+                args.use_uring(),
             ));
             println!("new file segment created")
         }
     }
+
+    if let (Some(window), Some(mut report)) = (window, report) {
+        report.finish(&window);
+        println!("{report}");
+    }
 }
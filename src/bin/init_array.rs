@@ -0,0 +1,59 @@
+use clap::Parser;
+
+use sdaa_data::{array::DeviceArray, sdr::LockPolicy};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(short = 'A', value_name = "remote ctrl ip:port of each board", required = true)]
+    remote_ctrl_addrs: Vec<String>,
+
+    #[clap(
+        short = 'L',
+        value_name = "local ctrl ip:port",
+        default_value = "0.0.0.0:3001"
+    )]
+    local_ctrl_addr: String,
+
+    #[clap(short = 'C')]
+    ignore_locking: bool,
+
+    #[clap(short = 'S', long = "start", help = "start streaming on all boards afterwards")]
+    start: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let array = DeviceArray::new(
+        args.remote_ctrl_addrs
+            .iter()
+            .map(|a| a.parse().expect("failed to parse remote ctrl addr"))
+            .collect(),
+        args.local_ctrl_addr
+            .parse()
+            .expect("failed to parse local ctrl addr"),
+    );
+
+    let lock_policy = LockPolicy {
+        ignore_locking: args.ignore_locking,
+        ..Default::default()
+    };
+    let result = array.bring_up(&lock_policy);
+    for board in array.status() {
+        println!("{board}");
+    }
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    if let Some(time_ref) = array.time_ref() {
+        println!(
+            "synced at {} +/- {:?}",
+            time_ref.epoch, time_ref.uncertainty
+        );
+    }
+    if args.start {
+        array.start_streaming().expect("failed to start streams");
+    }
+}
@@ -1,6 +1,6 @@
 use clap::Parser;
 
-use sdaa_data::sdr::SdrCtrl;
+use sdaa_data::sdr::{LockPolicy, SdrCtrl};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -20,14 +20,18 @@ fn main() {
     //let (tx,rx)=bounded(256);
     let args = Args::parse();
 
-    let sdr_ctrl = SdrCtrl {
-        remote_ctrl_addr: args.remote_ctrl_addr.parse().unwrap(),
-        local_ctrl_addr: args.local_ctrl_addr.parse().unwrap(),
-    };
+    let sdr_ctrl = SdrCtrl::new(
+        args.remote_ctrl_addr.parse().unwrap(),
+        args.local_ctrl_addr.parse().unwrap(),
+    );
 
     //std::thread::sleep(std::time::Duration::from_secs(2));
-    sdr_ctrl.stream_stop();
-    sdr_ctrl.wait_until_locked(60);
-    let summary = sdr_ctrl.init();
-    assert_eq!(summary.normal_reply.len(), 1);
+    sdr_ctrl.refresh_state().expect("failed to query device");
+    sdr_ctrl
+        .lock(&LockPolicy::default())
+        .expect("failed to lock");
+    if let Some(status) = sdr_ctrl.status() {
+        println!("{status}");
+    }
+    sdr_ctrl.initialize().expect("failed to init");
 }
@@ -0,0 +1,88 @@
+use std::{net::SocketAddr, time::Duration};
+
+use clap::Parser;
+use sdaa_data::{
+    mock_dev::{MockCtrlServer, MockDevice, MockDeviceConfig},
+    siggen::SignalConfig,
+    sim::{REAL_TIME_PKT_RATE, SimConfig},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(
+        short = 'A',
+        value_name = "ctrl ip:port to listen on",
+        default_value = "0.0.0.0:3000"
+    )]
+    ctrl_addr: String,
+
+    #[clap(short = 'a', value_name = "payload target ip:port")]
+    payload_addr: Option<String>,
+
+    #[clap(long = "fm-ver", default_value_t = 0)]
+    fm_ver: u32,
+
+    #[clap(long = "trans-state", value_parser = parse_u32, default_value = "0x2")]
+    trans_state: u32,
+
+    #[clap(long = "locked", value_parser = parse_u32, default_value = "0x3f")]
+    locked: u32,
+
+    #[clap(long = "health", value_parser = parse_u32, default_value = "0")]
+    health: u32,
+
+    #[clap(long = "lock-delay", value_name = "seconds", default_value_t = 2.0)]
+    lock_delay: f64,
+
+    #[clap(
+        short = 'r',
+        long = "rate",
+        value_name = "pkts per second, 0 for unpaced",
+        default_value_t = REAL_TIME_PKT_RATE
+    )]
+    rate: f64,
+
+    #[clap(long = "loss", value_name = "probability", default_value_t = 0.0)]
+    loss: f64,
+
+    #[clap(long = "noise", value_name = "noise rms", default_value_t = 0.0)]
+    noise_rms: f64,
+}
+
+fn parse_u32(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("invalid value {s}: {e}"))
+}
+
+fn main() {
+    let args = Args::parse();
+    let ctrl_addr: SocketAddr = args.ctrl_addr.parse().expect("failed to parse ctrl addr");
+    let stream = args.payload_addr.map(|addr| {
+        let sim = SimConfig {
+            rate: (args.rate > 0.0).then_some(args.rate),
+            loss: args.loss,
+            signal: SignalConfig {
+                noise_rms: args.noise_rms,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        (sim, addr.parse().expect("failed to parse payload addr"))
+    });
+
+    let device = MockDevice::new(MockDeviceConfig {
+        fm_ver: args.fm_ver,
+        trans_state: args.trans_state,
+        locked: args.locked,
+        health: args.health,
+        lock_delay: Duration::from_secs_f64(args.lock_delay),
+        stream,
+    });
+    let server = MockCtrlServer::spawn(ctrl_addr, device).expect("failed to bind ctrl addr");
+    println!("mock device listening on {}", server.local_addr());
+    server.join();
+}
@@ -0,0 +1,88 @@
+use std::net::SocketAddr;
+
+use clap::Parser;
+use crossbeam::channel::bounded;
+use sdaa_data::{
+    siggen::{Chirp, SignalConfig, Tone},
+    sim::{PacketSimulator, REAL_TIME_PKT_RATE, SimConfig},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(short = 'a', long = "addr", value_name = "target ip:port")]
+    target_addr: String,
+
+    #[clap(
+        short = 'r',
+        long = "rate",
+        value_name = "pkts per second, 0 for unpaced",
+        default_value_t = REAL_TIME_PKT_RATE
+    )]
+    rate: f64,
+
+    #[clap(short = 'p', value_name = "npkts to send")]
+    npkts: Option<u64>,
+
+    #[clap(long = "loss", value_name = "probability", default_value_t = 0.0)]
+    loss: f64,
+
+    #[clap(long = "reorder", value_name = "probability", default_value_t = 0.0)]
+    reorder: f64,
+
+    #[clap(long = "reorder-depth", value_name = "max pkts delayed", default_value_t = 8)]
+    reorder_depth: usize,
+
+    #[clap(long = "dup", value_name = "probability", default_value_t = 0.0)]
+    duplicate: f64,
+
+    #[clap(long = "restart-every", value_name = "npkts")]
+    restart_every: Option<u64>,
+
+    #[clap(long = "noise", value_name = "noise rms", default_value_t = 0.0)]
+    noise_rms: f64,
+
+    #[clap(long = "tone", value_name = "freq:amp")]
+    tones: Vec<Tone>,
+
+    #[clap(long = "chirp", value_name = "f0:f1:sweep:amp")]
+    chirps: Vec<Chirp>,
+
+    #[clap(long = "clip", value_name = "clipping level")]
+    clip: Option<i16>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let target: SocketAddr = args
+        .target_addr
+        .parse()
+        .expect("failed to parse target addr");
+
+    let config = SimConfig {
+        rate: (args.rate > 0.0).then_some(args.rate),
+        loss: args.loss,
+        reorder: args.reorder,
+        reorder_depth: args.reorder_depth,
+        duplicate: args.duplicate,
+        restart_every: args.restart_every,
+        npkts: args.npkts,
+        signal: SignalConfig {
+            noise_rms: args.noise_rms,
+            tones: args.tones,
+            chirps: args.chirps,
+            clip: args.clip,
+        },
+    };
+
+    let (tx_stop, rx_stop) = bounded(1);
+    ctrlc::set_handler(move || {
+        println!("Caught Ctrl+C");
+        let _ = tx_stop.try_send(());
+    })
+    .expect("Error setting Ctrl+C handler");
+
+    let mut sim = PacketSimulator::new(config, target).expect("failed to create socket");
+    let stats = sim.run(&rx_stop).expect("failed to send");
+    println!("{stats}");
+}
@@ -1,23 +1,37 @@
 #![allow(static_mut_refs)]
 
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    time::Duration,
+};
 
 use crossbeam::channel::{Receiver, Sender};
 use lockfree_object_pool::LinearOwnedReusable;
 use num::Complex;
 
 use crate::{
-    ddc::{M, N_PT_PER_FRAME}, payload::Payload, pipeline::{DdcCmd, RecvCmd}, sdr::{Sdr, RawSdr, SdrSmpRate}
+    ddc::{M, N_PT_PER_FRAME}, health::{DeviceEvent, DeviceEventKind}, payload::Payload, pipeline::{Block, DdcCmd, RecvCmd}, sdr::{LockPolicy, Sdr, RawSdr, SdrBuilder, SdrSmpRate},
+    stats::RecvStatsSnapshot,
 };
 
 pub const NDEC: usize = 4;
 
+/// Bring-up of the C entry points. As it always did, the device is used
+/// even if it does not lock within the timeout.
+fn c_lock_policy() -> LockPolicy {
+    LockPolicy {
+        ignore_locking: true,
+        ..Default::default()
+    }
+}
+
 pub struct CSdr {
     sdr_dev: Sdr,
-    rx_iq: Receiver<LinearOwnedReusable<Vec<Complex<f32>>>>,
+    rx_iq: Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
     tx_cmd: Sender<DdcCmd>,
-    buffer: Option<LinearOwnedReusable<Vec<Complex<f32>>>>,
+    buffer: Option<LinearOwnedReusable<Block<Complex<f32>>>>,
     cursor: usize,
+    events: Option<Receiver<DeviceEvent>>,
 }
 
 #[repr(C)]
@@ -27,6 +41,41 @@ pub struct CComplex {
     pub im: f32,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CDeviceEvent {
+    pub kind: DeviceEventKind,
+    /// Host time of the query in ns since the Unix epoch.
+    pub unix_ns: i64,
+    /// `locked` and `health` of the reply, 0 if the device did not answer.
+    pub locked: u32,
+    pub health: u32,
+}
+
+impl From<DeviceEvent> for CDeviceEvent {
+    fn from(event: DeviceEvent) -> Self {
+        Self {
+            kind: event.kind,
+            unix_ns: event.time.timestamp_nanos_opt().unwrap_or(0),
+            locked: event.status.map_or(0, |s| s.locked.raw),
+            health: event.status.map_or(0, |s| s.health),
+        }
+    }
+}
+
+fn poll_event(events: &Option<Receiver<DeviceEvent>>, event: *mut CDeviceEvent) -> bool {
+    match events.as_ref().and_then(|rx| rx.try_recv().ok()) {
+        Some(e) if !event.is_null() => {
+            unsafe { *event = e.into() };
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Brings the device up and starts the pipeline. Returns null if the device
+/// does not answer the query, init or sync, where it used to panic or carry
+/// on regardless.
 #[unsafe(no_mangle)]
 pub extern "C" fn new_sdr_device(
     remote_ctrl_ip: u32,
@@ -40,19 +89,19 @@ pub extern "C" fn new_sdr_device(
     let local_payload_addr =
         SocketAddrV4::new(Ipv4Addr::from(local_payload_ip), local_payload_port);
 
-    let (sdr_dev, rx_iq, tx_cmd) = Sdr::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr, SdrSmpRate::from_ndec(ndec));
-
-    if let Some(x)=sdr_dev.ctrl.awaken_and_locked(){
-        if !x{
-            //panic!();
-            sdr_dev.ctrl.wakeup();
-            sdr_dev.ctrl.wait_until_locked(60);    
+    let created = SdrSmpRate::from_ndec(ndec).and_then(|rate| {
+        SdrBuilder::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr)
+            .smp_rate(rate)
+            .bring_up(c_lock_policy())
+            .build()
+    });
+    let (sdr_dev, rx_iq, tx_cmd) = match created {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("failed to bring up device: {e}");
+            return std::ptr::null_mut();
         }
-    }else{
-        panic!("abnormal reply")
-    }
-    sdr_dev.ctrl.init();
-    sdr_dev.ctrl.sync();
+    };
 
     Box::into_raw(Box::new(CSdr {
         sdr_dev,
@@ -60,6 +109,7 @@ pub extern "C" fn new_sdr_device(
         tx_cmd,
         buffer: None,
         cursor: 0,
+        events: None,
     }))
 }
 
@@ -76,8 +126,10 @@ pub unsafe extern "C" fn free_sdr_device(csdr: *mut CSdr) {
             tx_cmd,
             buffer: _,
             cursor: _,
+            events: _,
         } = *obj;
-        tx_cmd.send(DdcCmd::Destroy).unwrap();
+        // the ddc thread may already be gone
+        let _ = tx_cmd.send(DdcCmd::Destroy);
         drop(tx_cmd);
         drop(rx_iq);
     }
@@ -86,29 +138,34 @@ pub unsafe extern "C" fn free_sdr_device(csdr: *mut CSdr) {
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
+/// Returns false if the ddc has stopped.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn set_lo_ch(csdr: *mut CSdr, lo_ch: i32) {
+pub unsafe extern "C" fn set_lo_ch(csdr: *mut CSdr, lo_ch: i32) -> bool {
     if csdr.is_null() {
-        return;
+        return false;
     }
 
     let obj = unsafe { &mut *csdr };
-    obj.tx_cmd.send(DdcCmd::LoCh(lo_ch as isize)).unwrap();
+    obj.tx_cmd.send(DdcCmd::LoCh(lo_ch as isize)).is_ok()
 }
 
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
+/// Returns false if the stream ended before `npt` points were written.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn fetch_data(csdr: *mut CSdr, buf: *mut CComplex, npt: usize) {
+pub unsafe extern "C" fn fetch_data(csdr: *mut CSdr, buf: *mut CComplex, npt: usize) -> bool {
     if csdr.is_null() {
-        return;
+        return false;
     }
 
     let obj = unsafe { &mut *csdr };
     let buf = unsafe { std::slice::from_raw_parts_mut(buf as *mut Complex<f32>, npt) };
     if obj.buffer.is_none() {
-        obj.buffer = Some(obj.rx_iq.recv().unwrap());
+        let Ok(block) = obj.rx_iq.recv() else {
+            return false;
+        };
+        obj.buffer = Some(block);
         obj.cursor = 0;
     }
 
@@ -117,7 +174,10 @@ pub unsafe extern "C" fn fetch_data(csdr: *mut CSdr, buf: *mut CComplex, npt: us
     while written < total {
         let available = obj.buffer.as_ref().unwrap().len() - obj.cursor;
         if available == 0 {
-            obj.buffer = Some(obj.rx_iq.recv().unwrap());
+            let Ok(block) = obj.rx_iq.recv() else {
+                return false;
+            };
+            obj.buffer = Some(block);
             obj.cursor = 0;
             continue;
         }
@@ -127,6 +187,7 @@ pub unsafe extern "C" fn fetch_data(csdr: *mut CSdr, buf: *mut CComplex, npt: us
         obj.cursor += copy_len;
         written += copy_len;
     }
+    true
 }
 
 /// # Safety
@@ -141,18 +202,42 @@ pub extern "C" fn get_mtu() -> usize {
 ///
 /// This function should not be called before the horsemen are ready.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn start_data_stream(csdr: *mut CSdr) {
+pub unsafe extern "C" fn start_data_stream(csdr: *mut CSdr) -> bool {
     let obj = unsafe { &mut *csdr };
-    obj.sdr_dev.ctrl.stream_start();
+    match obj.sdr_dev.control().and_then(|c| Ok(c.start_streaming()?)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("failed to start stream: {e}");
+            false
+        }
+    }
 }
 
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn stop_data_stream(csdr: *mut CSdr) {
+pub unsafe extern "C" fn stop_data_stream(csdr: *mut CSdr) -> bool {
     let obj = unsafe { &mut *csdr };
-    obj.sdr_dev.ctrl.stream_stop();
+    match obj.sdr_dev.control().and_then(|c| Ok(c.stop_streaming()?)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("failed to stop stream: {e}");
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_recv_stats(csdr: *mut CSdr, stats: *mut RecvStatsSnapshot) {
+    if csdr.is_null() || stats.is_null() {
+        return;
+    }
+    let obj = unsafe { &*csdr };
+    unsafe { *stats = obj.sdr_dev.recv_stats() };
 }
 
 pub struct CRawSdr {
@@ -161,9 +246,11 @@ pub struct CRawSdr {
     tx_cmd: Sender<RecvCmd>,
     buffer: Option<LinearOwnedReusable<Payload>>,
     cursor: usize,
+    events: Option<Receiver<DeviceEvent>>,
 }
 
 
+/// Same as [`new_sdr_device`] without the down conversion.
 #[unsafe(no_mangle)]
 pub extern "C" fn new_raw_sdr_device(
     remote_ctrl_ip: u32,
@@ -176,19 +263,16 @@ pub extern "C" fn new_raw_sdr_device(
     let local_payload_addr =
         SocketAddrV4::new(Ipv4Addr::from(local_payload_ip), local_payload_port);
 
-    let (sdr_dev, rx_payload, tx_cmd) = RawSdr::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr);
-
-    if let Some(x)=sdr_dev.ctrl.awaken_and_locked(){
-        if !x{
-            //panic!();
-            sdr_dev.ctrl.wakeup();
-            sdr_dev.ctrl.wait_until_locked(60);    
+    let created = SdrBuilder::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr)
+        .bring_up(c_lock_policy())
+        .build_raw();
+    let (sdr_dev, rx_payload, tx_cmd) = match created {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("failed to bring up device: {e}");
+            return std::ptr::null_mut();
         }
-    }else{
-        panic!("abnormal reply")
-    }
-    sdr_dev.ctrl.init();
-    sdr_dev.ctrl.sync();
+    };
 
     Box::into_raw(Box::new(CRawSdr {
         sdr_dev,
@@ -196,6 +280,7 @@ pub extern "C" fn new_raw_sdr_device(
         tx_cmd,
         buffer: None,
         cursor: 0,
+        events: None,
     }))
 }
 
@@ -213,8 +298,10 @@ pub unsafe extern "C" fn free_raw_sdr_device(csdr: *mut CRawSdr) {
             tx_cmd,
             buffer: _,
             cursor: _,
+            events: _,
         } = *obj;
-        tx_cmd.send(RecvCmd::Destroy).unwrap();
+        // the receiver may already be gone
+        let _ = tx_cmd.send(RecvCmd::Destroy);
         drop(tx_cmd);
         drop(rx_payload);
     }
@@ -223,16 +310,20 @@ pub unsafe extern "C" fn free_raw_sdr_device(csdr: *mut CRawSdr) {
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
+/// Returns false if the stream ended before `npt` points were written.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn fetch_raw_data(csdr: *mut CRawSdr, buf: *mut i16, npt: usize) {
+pub unsafe extern "C" fn fetch_raw_data(csdr: *mut CRawSdr, buf: *mut i16, npt: usize) -> bool {
     if csdr.is_null() {
-        return;
+        return false;
     }
 
     let obj = unsafe { &mut *csdr };
     let buf = unsafe { std::slice::from_raw_parts_mut(buf, npt) };
     if obj.buffer.is_none() {
-        obj.buffer = Some(obj.rx_payload.recv().unwrap());
+        let Ok(payload) = obj.rx_payload.recv() else {
+            return false;
+        };
+        obj.buffer = Some(payload);
         obj.cursor = 0;
     }
 
@@ -241,7 +332,10 @@ pub unsafe extern "C" fn fetch_raw_data(csdr: *mut CRawSdr, buf: *mut i16, npt:
     while written < total {
         let available = N_PT_PER_FRAME - obj.cursor;
         if available == 0 {
-            obj.buffer = Some(obj.rx_payload.recv().unwrap());
+            let Ok(payload) = obj.rx_payload.recv() else {
+                return false;
+            };
+            obj.buffer = Some(payload);
             obj.cursor = 0;
             continue;
         }
@@ -251,22 +345,105 @@ pub unsafe extern "C" fn fetch_raw_data(csdr: *mut CRawSdr, buf: *mut i16, npt:
         obj.cursor += copy_len;
         written += copy_len;
     }
+    true
 }
 
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn start_raw_data_stream(csdr: *mut CRawSdr) {
+pub unsafe extern "C" fn start_raw_data_stream(csdr: *mut CRawSdr) -> bool {
     let obj = unsafe { &mut *csdr };
-    obj.sdr_dev.ctrl.stream_start();
+    match obj.sdr_dev.control().and_then(|c| Ok(c.start_streaming()?)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("failed to start stream: {e}");
+            false
+        }
+    }
 }
 
 /// # Safety
 ///
 /// This function should not be called before the horsemen are ready.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn stop_raw_data_stream(csdr: *mut CRawSdr) {
+pub unsafe extern "C" fn stop_raw_data_stream(csdr: *mut CRawSdr) -> bool {
     let obj = unsafe { &mut *csdr };
-    obj.sdr_dev.ctrl.stream_stop();
+    match obj.sdr_dev.control().and_then(|c| Ok(c.stop_streaming()?)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("failed to stop stream: {e}");
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_raw_recv_stats(csdr: *mut CRawSdr, stats: *mut RecvStatsSnapshot) {
+    if csdr.is_null() || stats.is_null() {
+        return;
+    }
+    let obj = unsafe { &*csdr };
+    unsafe { *stats = obj.sdr_dev.recv_stats() };
+}
+
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn start_health_monitor(csdr: *mut CSdr, period_ms: u32) {
+    if csdr.is_null() {
+        return;
+    }
+    let obj = unsafe { &mut *csdr };
+    obj.events = obj
+        .sdr_dev
+        .start_health_monitor(Duration::from_millis(period_ms as u64))
+        .ok();
+}
+
+/// Takes the oldest pending device event, returns false if there is none.
+///
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poll_device_event(csdr: *mut CSdr, event: *mut CDeviceEvent) -> bool {
+    if csdr.is_null() {
+        return false;
+    }
+    let obj = unsafe { &*csdr };
+    poll_event(&obj.events, event)
+}
+
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn start_raw_health_monitor(csdr: *mut CRawSdr, period_ms: u32) {
+    if csdr.is_null() {
+        return;
+    }
+    let obj = unsafe { &mut *csdr };
+    obj.events = obj
+        .sdr_dev
+        .start_health_monitor(Duration::from_millis(period_ms as u64))
+        .ok();
+}
+
+/// Takes the oldest pending device event, returns false if there is none.
+///
+/// # Safety
+///
+/// This function should not be called before the horsemen are ready.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poll_raw_device_event(csdr: *mut CRawSdr, event: *mut CDeviceEvent) -> bool {
+    if csdr.is_null() {
+        return false;
+    }
+    let obj = unsafe { &*csdr };
+    poll_event(&obj.events, event)
 }
@@ -0,0 +1,198 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use crossbeam::channel::{RecvTimeoutError, Sender, bounded};
+
+use crate::{RAW_SAMP_RATE, sdr::SdrCtrl, time_ref::TimeReference, transport::CtrlTransport};
+
+/// Nominal rate of the device tick counter. The counter is assumed, not
+/// known from the firmware, to count raw samples, i.e. to run off the same
+/// reference as the sample clock and to restart at the sync, so that tick
+/// `n` is raw sample `n` of the stream. Nothing reads the tick of a packet,
+/// so a fit is only trusted while it agrees with the sync, see
+/// [`ClockFit::agrees_with`].
+pub const NOMINAL_TICK_RATE: f64 = RAW_SAMP_RATE as f64;
+
+/// Largest relative deviation from [`NOMINAL_TICK_RATE`] a fit may show,
+/// 100 ppm, well above what the sample clock reference drifts. More means
+/// the counter does not count samples.
+pub const MAX_CLOCK_DRIFT: f64 = 1e-4;
+
+/// Number of query points the fit is made over.
+pub const MAX_CLOCK_POINTS: usize = 256;
+
+/// Linear relation between the device tick counter and host UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockFit {
+    /// Host time of tick 0.
+    pub tick_epoch: DateTime<Utc>,
+    /// Standard error of `tick_epoch`.
+    pub uncertainty: Duration,
+    /// Ticks per host second.
+    pub tick_rate: f64,
+    /// Relative deviation of `tick_rate` from the nominal rate.
+    pub drift: f64,
+    pub n_points: usize,
+}
+
+impl ClockFit {
+    pub fn host_time(&self, ticks: u64) -> DateTime<Utc> {
+        self.tick_epoch + TimeDelta::nanoseconds((ticks as f64 * 1e9 / self.tick_rate).round() as i64)
+    }
+
+    /// Whether the fit can stand in for `time_ref`: the fitted time of tick 0
+    /// lies within the uncertainty of the sync epoch and the drift is below
+    /// [`MAX_CLOCK_DRIFT`]. Otherwise the ticks did not restart at the sync
+    /// or do not run at the nominal rate.
+    pub fn agrees_with(&self, time_ref: &TimeReference) -> bool {
+        let offset = (self.tick_epoch - time_ref.epoch).abs();
+        offset.to_std().is_ok_and(|d| d <= time_ref.uncertainty)
+            && self.drift.abs() < MAX_CLOCK_DRIFT
+    }
+
+    /// Replaces the epoch and sample rate of a stream synced at tick 0 by
+    /// the fitted ones, or returns `time_ref` unchanged if the fit does not
+    /// [agree](Self::agrees_with) with it.
+    pub fn correct(&self, time_ref: &TimeReference) -> TimeReference {
+        if !self.agrees_with(time_ref) {
+            return *time_ref;
+        }
+        TimeReference {
+            epoch: self.tick_epoch,
+            uncertainty: self.uncertainty,
+            samp_rate: time_ref.samp_rate * (1.0 + self.drift),
+        }
+    }
+}
+
+/// Least squares fit of host time against device ticks over the most
+/// recent [`MAX_CLOCK_POINTS`] queries.
+pub struct ClockEstimator {
+    nominal_rate: f64,
+    points: VecDeque<(DateTime<Utc>, u64)>,
+}
+
+impl ClockEstimator {
+    pub fn new(nominal_rate: f64) -> Self {
+        Self {
+            nominal_rate,
+            points: VecDeque::with_capacity(MAX_CLOCK_POINTS),
+        }
+    }
+
+    /// Adds a query reply with `ticks` received at host time `host`. A tick
+    /// count going backwards means the device was synced again, so the
+    /// history is discarded.
+    pub fn add(&mut self, host: DateTime<Utc>, ticks: u64) {
+        if let Some(&(_, last)) = self.points.back()
+            && ticks < last
+        {
+            self.points.clear();
+        }
+        if self.points.len() == MAX_CLOCK_POINTS {
+            self.points.pop_front();
+        }
+        self.points.push_back((host, ticks));
+    }
+
+    pub fn fit(&self) -> Option<ClockFit> {
+        let n = self.points.len();
+        if n < 3 {
+            return None;
+        }
+        let &(h0, k0) = self.points.front()?;
+        // relative to the first point to keep the precision of f64
+        let xy: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .map(|&(h, k)| {
+                let dh = (h - h0).num_nanoseconds().unwrap_or(i64::MAX) as f64 * 1e-9;
+                ((k - k0) as f64, dh)
+            })
+            .collect();
+        let nf = n as f64;
+        let xm = xy.iter().map(|p| p.0).sum::<f64>() / nf;
+        let ym = xy.iter().map(|p| p.1).sum::<f64>() / nf;
+        let sxx = xy.iter().map(|p| (p.0 - xm).powi(2)).sum::<f64>();
+        let sxy = xy.iter().map(|p| (p.0 - xm) * (p.1 - ym)).sum::<f64>();
+        if sxx == 0.0 || sxy <= 0.0 {
+            return None;
+        }
+        let slope = sxy / sxx;
+        let intercept = ym - slope * xm;
+        let rss = xy
+            .iter()
+            .map(|p| (p.1 - intercept - slope * p.0).powi(2))
+            .sum::<f64>();
+        let sigma = (rss / (nf - 2.0)).sqrt();
+
+        // host time of tick 0, i.e. x = -k0
+        let x0 = -(k0 as f64);
+        let t0 = intercept + slope * x0;
+        let err = sigma * (1.0 / nf + (x0 - xm).powi(2) / sxx).sqrt();
+        let tick_rate = 1.0 / slope;
+        Some(ClockFit {
+            tick_epoch: h0 + TimeDelta::nanoseconds((t0 * 1e9).round() as i64),
+            uncertainty: Duration::from_secs_f64(err),
+            tick_rate,
+            drift: tick_rate / self.nominal_rate - 1.0,
+            n_points: n,
+        })
+    }
+}
+
+/// Background task querying the device every `period` and keeping a
+/// [`ClockFit`] up to date. Stops when dropped.
+pub struct ClockDiscipline {
+    fit: Arc<Mutex<Option<ClockFit>>>,
+    tx_stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ClockDiscipline {
+    pub fn spawn<T: CtrlTransport + Send + Sync + 'static>(
+        ctrl: Arc<SdrCtrl<T>>,
+        period: Duration,
+        nominal_tick_rate: f64,
+    ) -> Self {
+        let fit = Arc::new(Mutex::new(None));
+        let (tx_stop, rx_stop) = bounded::<()>(1);
+        let fit1 = Arc::clone(&fit);
+        let thread = std::thread::spawn(move || {
+            let mut estimator = ClockEstimator::new(nominal_tick_rate);
+            loop {
+                if let Some((host, ticks)) = ctrl.query_ticks() {
+                    estimator.add(host, ticks);
+                    *fit1.lock().unwrap() = estimator.fit();
+                }
+                match rx_stop.recv_timeout(period) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+        });
+        Self {
+            fit,
+            tx_stop: Some(tx_stop),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn fit(&self) -> Option<ClockFit> {
+        *self.fit.lock().unwrap()
+    }
+}
+
+impl Drop for ClockDiscipline {
+    fn drop(&mut self) {
+        drop(self.tx_stop.take());
+        if let Some(h) = self.thread.take() {
+            let _ = h.join();
+        }
+    }
+}
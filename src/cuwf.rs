@@ -1,4 +1,4 @@
-use crate::ddc::N_PT_PER_FRAME;
+use crate::{ddc::N_PT_PER_FRAME, error::SdaaError};
 
 pub struct WfResource{
     pub res:*mut crate::bindings::cuwf::Resource,
@@ -16,12 +16,17 @@ impl Drop for WfResource {
 }
 
 impl WfResource {
-    pub fn new(nch: usize, nbatch: usize, nint: usize) -> Self {
+    pub fn new(nch: usize, nbatch: usize, nint: usize) -> Result<Self, SdaaError> {
+        if nint == 0 || !nbatch.is_multiple_of(nint) {
+            return Err(SdaaError::InvalidConfig(format!(
+                "nbatch {nbatch} is not a multiple of nint {nint}"
+            )));
+        }
         let res = unsafe { crate::bindings::cuwf::init_resource(nch as i32, N_PT_PER_FRAME as i32, nbatch as i32, nint as i32) };
         if res.is_null() {
-            panic!("Failed to create resource");
+            return Err(SdaaError::CudaInit("init_resource"));
         }
-        Self { res, nch, nbatch, nint }
+        Ok(Self { res, nch, nbatch, nint })
     }
 
     pub fn process(&mut self, input: &[i16], output: &mut [f32])->bool {
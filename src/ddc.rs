@@ -5,6 +5,7 @@ pub use crate::{
     bindings::ddc::{self, DDCResources},
     payload::N_PT_PER_FRAME,
 };
+use crate::error::SdaaError;
 use std::{os::raw::c_int, sync::{Arc, Mutex}};
 
 unsafe impl Send for crate::bindings::ddc::fcomplex {}
@@ -22,9 +23,14 @@ unsafe impl Send for DownConverter {}
 
 impl DownConverter {
     #[allow(clippy::arc_with_non_send_sync)]
-    pub fn new(ndec: usize, fir_coeffs: &[f32]) -> Self {
+    pub fn new(ndec: usize, fir_coeffs: &[f32]) -> Result<Self, SdaaError> {
+        if ndec == 0 || !fir_coeffs.len().is_multiple_of(ndec) {
+            return Err(SdaaError::InvalidConfig(format!(
+                "{} fir taps are not a multiple of ndec {ndec}",
+                fir_coeffs.len()
+            )));
+        }
         let k = fir_coeffs.len() / ndec;
-        assert_eq!(ndec * k, fir_coeffs.len());
 
 
         let res=unsafe {
@@ -36,11 +42,20 @@ impl DownConverter {
                 fir_coeffs.as_ptr(),
             )
         };
-        Self(Arc::new(Mutex::new(res)))
+        if res.is_null() {
+            return Err(SdaaError::CudaInit("init_ddc_resources"));
+        }
+        Ok(Self(Arc::new(Mutex::new(res))))
     }
 
-    pub fn ddc(&mut self, indata: &[i16], lo_ch: isize) -> bool {
-        assert_eq!(indata.len(), N_PT_PER_FRAME);
+    /// Feeds one frame, true once an output block is ready to fetch.
+    pub fn ddc(&mut self, indata: &[i16], lo_ch: isize) -> Result<bool, SdaaError> {
+        if indata.len() != N_PT_PER_FRAME {
+            return Err(SdaaError::InvalidConfig(format!(
+                "frame of {} samples instead of {N_PT_PER_FRAME}",
+                indata.len()
+            )));
+        }
 
         let result = unsafe {
             crate::bindings::ddc::ddc(
@@ -49,8 +64,13 @@ impl DownConverter {
                 *self.0.lock().unwrap(),
             )
         };
-        assert!(result >= 0);
-        result != 0
+        if result < 0 {
+            return Err(SdaaError::Cuda {
+                call: "ddc",
+                code: result as i64,
+            });
+        }
+        Ok(result != 0)
     }
 
     pub fn fetch_output(&mut self, outdata: &mut [Complex<f32>]) {
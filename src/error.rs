@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+use crossbeam::channel::SendError;
+
+use crate::{array::ArrayError, sdr::DeviceError};
+
+/// Everything that can go wrong in this crate short of a bug.
+#[derive(Debug)]
+pub enum SdaaError {
+    /// Socket setup or file I/O.
+    Io(std::io::Error),
+    /// A control step on a single device.
+    Device(DeviceError),
+    /// A control step on a [`DeviceArray`](crate::array::DeviceArray).
+    Array(ArrayError),
+    /// A CUDA library call failed, `code` is what it returned.
+    Cuda { call: &'static str, code: i64 },
+    /// A CUDA library call that allocates resources returned null.
+    CudaInit(&'static str),
+    /// Parameters that cannot work together, e.g. a decimation the filter
+    /// length is not a multiple of.
+    InvalidConfig(String),
+    /// The other end of a pipeline channel is gone.
+    ChannelClosed,
+    /// Needs the control connection of a device that is only listened to,
+    /// see [`SdrBuilder::listen_only`](crate::sdr::SdrBuilder::listen_only).
+    NoControl,
+}
+
+impl Display for SdaaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SdaaError::Io(e) => write!(f, "I/O error: {e}"),
+            SdaaError::Device(e) => write!(f, "device error: {e}"),
+            SdaaError::Array(e) => write!(f, "device array error: {e}"),
+            SdaaError::Cuda { call, code } => write!(f, "{call} failed with {code}"),
+            SdaaError::CudaInit(call) => write!(f, "{call} could not allocate its resources"),
+            SdaaError::InvalidConfig(msg) => write!(f, "invalid configuration: {msg}"),
+            SdaaError::ChannelClosed => write!(f, "pipeline channel closed"),
+            SdaaError::NoControl => write!(f, "no control connection, only listening"),
+        }
+    }
+}
+
+impl std::error::Error for SdaaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdaaError::Io(e) => Some(e),
+            SdaaError::Device(e) => Some(e),
+            SdaaError::Array(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SdaaError {
+    fn from(e: std::io::Error) -> Self {
+        SdaaError::Io(e)
+    }
+}
+
+impl From<DeviceError> for SdaaError {
+    fn from(e: DeviceError) -> Self {
+        SdaaError::Device(e)
+    }
+}
+
+impl From<ArrayError> for SdaaError {
+    fn from(e: ArrayError) -> Self {
+        SdaaError::Array(e)
+    }
+}
+
+impl<T> From<SendError<T>> for SdaaError {
+    fn from(_: SendError<T>) -> Self {
+        SdaaError::ChannelClosed
+    }
+}
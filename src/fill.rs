@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use rand::{Rng, rngs::ThreadRng};
+use rand_distr::Normal;
+
+use crate::payload::{N_PT_PER_FRAME, Payload};
+
+/// What `recv_pkt` puts in place of a packet that never arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GapFill {
+    /// All-zero frame.
+    #[default]
+    Zeros,
+    /// Gaussian noise with the RMS of the recently received frames.
+    Noise,
+    /// Copy of the last received frame.
+    RepeatLast,
+    /// Emit nothing, consumers see a jump in `pkt_cnt`.
+    PassThrough,
+}
+
+impl FromStr for GapFill {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zeros" => Ok(GapFill::Zeros),
+            "noise" => Ok(GapFill::Noise),
+            "hold" | "repeat" => Ok(GapFill::RepeatLast),
+            "none" | "pass" => Ok(GapFill::PassThrough),
+            _ => Err(format!("unknown gap fill mode {s}, expected zeros, noise, hold or none")),
+        }
+    }
+}
+
+/// Weight of the newest frame in the running mean square used by [`GapFill::Noise`].
+const RMS_ALPHA: f64 = 1.0 / 16.0;
+
+pub struct GapFiller {
+    mode: GapFill,
+    mean_square: Option<f64>,
+    last: Box<[i16; N_PT_PER_FRAME]>,
+    rng: ThreadRng,
+}
+
+impl GapFiller {
+    pub fn new(mode: GapFill) -> Self {
+        Self {
+            mode,
+            mean_square: None,
+            last: Box::new([0; N_PT_PER_FRAME]),
+            rng: rand::rng(),
+        }
+    }
+
+    pub fn mode(&self) -> GapFill {
+        self.mode
+    }
+
+    /// Feeds a received frame, only does work for the modes that need it.
+    pub fn observe(&mut self, payload: &Payload) {
+        match self.mode {
+            GapFill::Noise => {
+                let ms = payload
+                    .data
+                    .iter()
+                    .map(|&x| (x as f64) * (x as f64))
+                    .sum::<f64>()
+                    / N_PT_PER_FRAME as f64;
+                self.mean_square = Some(match self.mean_square {
+                    Some(old) => old + RMS_ALPHA * (ms - old),
+                    None => ms,
+                });
+            }
+            GapFill::RepeatLast => self.last.copy_from_slice(&payload.data),
+            GapFill::Zeros | GapFill::PassThrough => {}
+        }
+    }
+
+    /// Fills the data of a frame taken from the pool, which is already zeroed.
+    /// Returns false if the frame should not be emitted at all.
+    pub fn fill(&mut self, payload: &mut Payload) -> bool {
+        match self.mode {
+            GapFill::Zeros => true,
+            GapFill::Noise => {
+                let rms = self.mean_square.unwrap_or(0.0).sqrt() as f32;
+                if let Ok(dist) = Normal::new(0.0_f32, rms) {
+                    payload.data.iter_mut().for_each(|x| {
+                        *x = self.rng.sample(dist).round().clamp(i16::MIN as f32, i16::MAX as f32)
+                            as i16;
+                    });
+                }
+                true
+            }
+            GapFill::RepeatLast => {
+                payload.data.copy_from_slice(&self.last[..]);
+                true
+            }
+            GapFill::PassThrough => false,
+        }
+    }
+}
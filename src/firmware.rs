@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use crate::{
+    payload::{KNOWN_VERSIONS, PayloadValidator},
+    sdr::DeviceError,
+};
+
+/// A firmware release and the `Payload.version` it streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareCompat {
+    /// `fm_ver` of the query reply.
+    pub fm_ver: u32,
+    pub payload_version: u32,
+}
+
+/// Firmware releases and their payload version, none yet. Add a line per
+/// release from its release notes, a release whose payload version is not
+/// in [`KNOWN_VERSIONS`] is then refused by [`FirmwareCheck::Refuse`].
+/// Firmware missing here is unknown and only warned about.
+pub const KNOWN_FIRMWARE: &[FirmwareCompat] = &[];
+
+/// `Payload.version` sent by firmware `fm_ver`, `None` if it is not in
+/// [`KNOWN_FIRMWARE`].
+pub fn payload_version(fm_ver: u32) -> Option<u32> {
+    KNOWN_FIRMWARE
+        .iter()
+        .find(|c| c.fm_ver == fm_ver)
+        .map(|c| c.payload_version)
+}
+
+/// Whether the packets of firmware `fm_ver` can be parsed by this build.
+pub fn is_supported(fm_ver: u32) -> bool {
+    payload_version(fm_ver).is_some_and(|v| KNOWN_VERSIONS.contains(&v))
+}
+
+/// Whether firmware `fm_ver` is known to stream packets this build cannot parse.
+pub fn is_incompatible(fm_ver: u32) -> bool {
+    payload_version(fm_ver).is_some_and(|v| !KNOWN_VERSIONS.contains(&v))
+}
+
+impl PayloadValidator {
+    /// Accepts only the payload version of firmware `fm_ver`, or
+    /// any version if the firmware is unknown.
+    pub fn for_firmware(fm_ver: u32) -> Self {
+        match payload_version(fm_ver) {
+            Some(v) => Self::new(&[v]),
+            None => Self::default(),
+        }
+    }
+}
+
+/// What to do when the device runs firmware that is not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FirmwareCheck {
+    /// The default while [`KNOWN_FIRMWARE`] is empty.
+    #[default]
+    Ignore,
+    /// Print a warning and carry on.
+    Warn,
+    /// Fail the bring-up with [`DeviceError::UnsupportedFirmware`] if the
+    /// firmware is known to be incompatible, warn if it is unknown.
+    Refuse,
+}
+
+impl FromStr for FirmwareCheck {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(FirmwareCheck::Ignore),
+            "warn" => Ok(FirmwareCheck::Warn),
+            "refuse" => Ok(FirmwareCheck::Refuse),
+            _ => Err(format!("unknown firmware check {s}, expected ignore, warn or refuse")),
+        }
+    }
+}
+
+impl FirmwareCheck {
+    pub fn verify(&self, fm_ver: u32) -> Result<(), DeviceError> {
+        if is_supported(fm_ver) {
+            return Ok(());
+        }
+        match self {
+            FirmwareCheck::Ignore => Ok(()),
+            FirmwareCheck::Refuse if is_incompatible(fm_ver) => {
+                Err(DeviceError::UnsupportedFirmware(fm_ver))
+            }
+            FirmwareCheck::Warn | FirmwareCheck::Refuse => {
+                eprintln!("warning: firmware {fm_ver:#x} is not known to work with this build");
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, TrySendError, bounded};
+
+use crate::{sdr::SdrCtrl, status::DeviceStatus, transport::CtrlTransport};
+
+/// Events queued per subscriber before further ones are dropped.
+pub const EVENT_QUEUE_LEN: usize = 64;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEventKind {
+    /// `locked` stopped matching, see [`crate::status::LockState::is_locked`].
+    LockLost,
+    LockRegained,
+    /// `health` changed. Not judged either way, the new value is in the
+    /// status.
+    HealthChanged,
+    /// A query got no valid reply.
+    Unreachable,
+    Reachable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceEvent {
+    pub kind: DeviceEventKind,
+    /// Host time of the query that noticed the change.
+    pub time: DateTime<Utc>,
+    /// The reply of that query, `None` for [`DeviceEventKind::Unreachable`].
+    pub status: Option<DeviceStatus>,
+}
+
+impl Display for DeviceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?}", self.time, self.kind)?;
+        if let Some(status) = self.status {
+            write!(
+                f,
+                " locked={:#x} health={:#x}",
+                status.locked.raw, status.health
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What the monitor last saw. Starts out assuming a locked device with
+/// `health` 0, so a monitor started on any other one reports it right away.
+struct Tracked {
+    reachable: bool,
+    locked: bool,
+    health: u32,
+}
+
+impl Default for Tracked {
+    fn default() -> Self {
+        Self {
+            reachable: true,
+            locked: true,
+            health: 0,
+        }
+    }
+}
+
+impl Tracked {
+    fn update(&mut self, status: Option<DeviceStatus>, time: DateTime<Utc>) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        let mut push = |kind| events.push(DeviceEvent { kind, time, status });
+        let Some(s) = status else {
+            if self.reachable {
+                push(DeviceEventKind::Unreachable);
+            }
+            self.reachable = false;
+            return events;
+        };
+        if !self.reachable {
+            push(DeviceEventKind::Reachable);
+        }
+        if self.locked && !s.is_locked() {
+            push(DeviceEventKind::LockLost);
+        } else if !self.locked && s.is_locked() {
+            push(DeviceEventKind::LockRegained);
+        }
+        if s.health != self.health {
+            push(DeviceEventKind::HealthChanged);
+        }
+        *self = Self {
+            reachable: true,
+            locked: s.is_locked(),
+            health: s.health,
+        };
+        events
+    }
+}
+
+/// Queries the device every `period` in the background and publishes
+/// lock and health changes to every subscriber.
+pub struct HealthMonitor {
+    subscribers: Arc<Mutex<Vec<Sender<DeviceEvent>>>>,
+    last: Arc<Mutex<Option<DeviceStatus>>>,
+    tx_stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HealthMonitor {
+    pub fn spawn<T: CtrlTransport + Send + Sync + 'static>(
+        ctrl: Arc<SdrCtrl<T>>,
+        period: Duration,
+    ) -> Self {
+        let subscribers = Arc::new(Mutex::new(Vec::<Sender<DeviceEvent>>::new()));
+        let last = Arc::new(Mutex::new(None));
+        let (tx_stop, rx_stop) = bounded::<()>(1);
+        let subscribers1 = Arc::clone(&subscribers);
+        let last1 = Arc::clone(&last);
+        let thread = std::thread::spawn(move || {
+            let mut tracked = Tracked::default();
+            // wait a period before the first query so subscribers taken
+            // right after spawning see its events
+            while let Err(RecvTimeoutError::Timeout) = rx_stop.recv_timeout(period) {
+                let status = ctrl.status();
+                *last1.lock().unwrap() = status;
+                for event in tracked.update(status, Utc::now()) {
+                    // a slow subscriber only loses its own events, a gone one is forgotten
+                    subscribers1.lock().unwrap().retain(|tx| {
+                        !matches!(tx.try_send(event), Err(TrySendError::Disconnected(_)))
+                    });
+                }
+            }
+        });
+        Self {
+            subscribers,
+            last,
+            tx_stop: Some(tx_stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// New channel receiving every event from now on.
+    pub fn subscribe(&self) -> Receiver<DeviceEvent> {
+        let (tx, rx) = bounded(EVENT_QUEUE_LEN);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Reply to the latest query, `None` before the first one or if it got no reply.
+    pub fn last_status(&self) -> Option<DeviceStatus> {
+        *self.last.lock().unwrap()
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        drop(self.tx_stop.take());
+        if let Some(h) = self.thread.take() {
+            let _ = h.join();
+        }
+    }
+}
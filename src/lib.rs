@@ -1,8 +1,21 @@
 #![feature(portable_simd)]
 
+pub mod array;
+pub mod arrival;
+pub mod clock;
+pub mod error;
+pub mod fill;
 pub mod fir;
+pub mod firmware;
+pub mod health;
+pub mod meta;
 pub mod payload;
 pub mod pipeline;
+pub mod recovery;
+pub mod reorder;
+pub mod siggen;
+pub mod sim;
+pub mod source;
 pub mod utils;
 
 #[cfg(feature = "cuda")]
@@ -15,6 +28,19 @@ pub mod ddc;
 pub mod c_interface;
 
 pub mod sdr;
+pub mod stats;
+pub mod status;
+pub mod time_ref;
+pub mod transport;
+pub mod window;
+
+#[cfg(feature = "io_uring")]
+pub mod uring;
+
+// relies on `CtrlMsg::CmdReply`, `CtrlMsg::from_bytes` and `CtrlMsg::to_bytes`,
+// which the released sdaa_ctrl is not confirmed to provide
+#[cfg(feature = "mock_dev")]
+pub mod mock_dev;
 
 #[cfg(feature = "cuda")]
 pub mod cuwf;
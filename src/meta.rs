@@ -0,0 +1,83 @@
+use std::{fmt::Display, fs::File, io::Write, path::Path};
+
+use crate::{firmware, time_ref::TimeReference};
+
+/// Sidecar written next to a capture file as `<file>.meta`, one
+/// `key = value` per line, so the data can be time tagged afterwards.
+pub struct CaptureMeta {
+    pub time_ref: TimeReference,
+    /// Raw sample index of the first sample in the file.
+    pub first_sample: u64,
+    /// `fm_ver` of the device, if it was queried.
+    pub fm_ver: Option<u32>,
+    /// `Payload.version` of the stream, derived from `fm_ver` if not set.
+    pub payload_version: Option<u32>,
+    entries: Vec<(String, String)>,
+}
+
+impl CaptureMeta {
+    pub fn new(time_ref: TimeReference, first_sample: u64) -> Self {
+        Self {
+            time_ref,
+            first_sample,
+            fm_ver: None,
+            payload_version: None,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn with_firmware(mut self, fm_ver: Option<u32>) -> Self {
+        self.fm_ver = fm_ver;
+        self
+    }
+
+    pub fn with_payload_version(mut self, version: Option<u32>) -> Self {
+        self.payload_version = version;
+        self
+    }
+
+    /// Adds a free-form entry such as the decimation or the LO channel.
+    pub fn with(mut self, key: &str, value: impl Display) -> Self {
+        self.entries.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn meta_path(data_path: &str) -> String {
+        format!("{data_path}.meta")
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let tref = &self.time_ref;
+        writeln!(w, "epoch_utc = {}", tref.epoch.to_rfc3339())?;
+        writeln!(w, "epoch_uncertainty = {:e}", tref.uncertainty.as_secs_f64())?;
+        writeln!(w, "raw_samp_rate = {}", tref.samp_rate)?;
+        writeln!(w, "first_sample = {}", self.first_sample)?;
+        writeln!(
+            w,
+            "start_utc = {}",
+            tref.sample_time(self.first_sample).to_rfc3339()
+        )?;
+        writeln!(w, "start_mjd = {:.12}", tref.sample_mjd(self.first_sample))?;
+        match self.fm_ver {
+            Some(v) => writeln!(w, "fm_ver = {v:#x}")?,
+            None => writeln!(w, "fm_ver = unknown")?,
+        }
+        match self
+            .payload_version
+            .or_else(|| self.fm_ver.and_then(firmware::payload_version))
+        {
+            Some(v) => writeln!(w, "payload_version = {v}")?,
+            None => writeln!(w, "payload_version = unknown")?,
+        }
+        for (k, v) in &self.entries {
+            writeln!(w, "{k} = {v}")?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, data_path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = Self::meta_path(&data_path.as_ref().to_string_lossy());
+        let mut f = File::create(path)?;
+        self.write_to(&mut f)
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    net::{SocketAddr, SocketAddrV4, UdpSocket},
+    sync::Mutex,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossbeam::channel::{Sender, TryRecvError, bounded};
+use sdaa_ctrl::ctrl_msg::CtrlMsg;
+
+use crate::{
+    clock::NOMINAL_TICK_RATE,
+    sim::{SimConfig, SimHandle},
+    status::DeviceStatus,
+    transport::{CmdReplies, CtrlTransport},
+};
+
+/// What a [`MockDevice`] reports and how it behaves.
+#[derive(Debug, Clone)]
+pub struct MockDeviceConfig {
+    pub fm_ver: u32,
+    /// `trans_state` once powered up, 0 before.
+    pub trans_state: u32,
+    /// `locked` once the lock delay has passed, 0 before.
+    pub locked: u32,
+    pub health: u32,
+    /// Time from `PwrCtrl` to reporting lock.
+    pub lock_delay: Duration,
+    /// Payload stream started on `StreamStart`, if any.
+    pub stream: Option<(SimConfig, SocketAddr)>,
+}
+
+impl Default for MockDeviceConfig {
+    fn default() -> Self {
+        Self {
+            fm_ver: 0,
+            trans_state: 0b10,
+            locked: 0x3f,
+            health: 0,
+            lock_delay: Duration::from_secs(2),
+            stream: None,
+        }
+    }
+}
+
+/// Device side of the control protocol, without the network.
+pub struct MockDevice {
+    config: MockDeviceConfig,
+    powered_at: Option<Instant>,
+    synced_at: Option<Instant>,
+    initialized: bool,
+    stream: Option<SimHandle>,
+}
+
+impl MockDevice {
+    pub fn new(config: MockDeviceConfig) -> Self {
+        Self {
+            config,
+            powered_at: None,
+            synced_at: None,
+            initialized: false,
+            stream: None,
+        }
+    }
+
+    /// Changes apply to the following replies, e.g. clearing `locked` or
+    /// setting `health` to inject a fault.
+    pub fn config_mut(&mut self) -> &mut MockDeviceConfig {
+        &mut self.config
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.powered_at
+            .is_some_and(|t| t.elapsed() >= self.config.lock_delay)
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.stream.as_ref().is_some_and(|s| !s.is_finished())
+    }
+
+    fn ticks(&self) -> u64 {
+        self.synced_at
+            .map_or(0, |t| (t.elapsed().as_secs_f64() * NOMINAL_TICK_RATE) as u64)
+    }
+
+    fn start_stream(&mut self) {
+        self.stop_stream();
+        if let Some((ref sim, target)) = self.config.stream {
+            self.stream = Some(SimHandle::spawn(sim.clone(), target));
+        }
+    }
+
+    fn stop_stream(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            match stream.stop() {
+                Ok(stats) => println!("stream stopped: {stats}"),
+                Err(e) => eprintln!("stream failed: {e}"),
+            }
+        }
+    }
+
+    /// Reply to one command, `None` for messages a device never receives.
+    pub fn handle(&mut self, msg: &CtrlMsg) -> Option<CtrlMsg> {
+        let msg_id = match *msg {
+            CtrlMsg::Query { msg_id } => {
+                let ticks = self.ticks();
+                return Some(CtrlMsg::QueryReply {
+                    msg_id,
+                    fm_ver: self.config.fm_ver,
+                    tick_cnt1: ticks as u32,
+                    tick_cnt2: (ticks >> 32) as u32,
+                    trans_state: if self.powered_at.is_some() {
+                        self.config.trans_state
+                    } else {
+                        0
+                    },
+                    locked: if self.is_locked() { self.config.locked } else { 0 },
+                    health: self.config.health,
+                });
+            }
+            CtrlMsg::PwrCtrl { msg_id, op_code } => {
+                if op_code == 0 {
+                    self.stop_stream();
+                    self.powered_at = None;
+                    self.initialized = false;
+                } else if self.powered_at.is_none() {
+                    self.powered_at = Some(Instant::now());
+                }
+                msg_id
+            }
+            CtrlMsg::Init { msg_id, .. } => {
+                self.initialized = true;
+                msg_id
+            }
+            CtrlMsg::Sync { msg_id } => {
+                self.synced_at = Some(Instant::now());
+                // pkt_cnt restarts at the sync
+                if self.stream.is_some() {
+                    self.start_stream();
+                }
+                msg_id
+            }
+            CtrlMsg::StreamStart { msg_id } => {
+                self.start_stream();
+                msg_id
+            }
+            CtrlMsg::StreamStop { msg_id } => {
+                self.stop_stream();
+                msg_id
+            }
+            _ => return None,
+        };
+        Some(CtrlMsg::CmdReply {
+            msg_id,
+            err_code: 0,
+        })
+    }
+}
+
+/// In-process transport talking straight to a [`MockDevice`], every remote
+/// address reaches the same device.
+pub struct FakeTransport {
+    pub device: Mutex<MockDevice>,
+}
+
+impl FakeTransport {
+    pub fn new(device: MockDevice) -> Self {
+        Self {
+            device: Mutex::new(device),
+        }
+    }
+}
+
+impl CtrlTransport for FakeTransport {
+    fn exchange(
+        &self,
+        cmd: CtrlMsg,
+        remote: &[SocketAddrV4],
+        _timeout: Duration,
+    ) -> CmdReplies {
+        let mut device = self.device.lock().unwrap();
+        let mut replies = CmdReplies::default();
+        for &addr in remote {
+            if let Some(reply) = device.handle(&cmd) {
+                replies
+                    .normal
+                    .push((addr.into(), DeviceStatus::from_reply(&reply)));
+            }
+        }
+        replies
+    }
+}
+
+/// In-process transport to several [`MockDevice`]s, one per address.
+/// Addresses without a device never reply.
+pub struct FakeArrayTransport {
+    pub devices: Vec<(SocketAddrV4, Mutex<MockDevice>)>,
+}
+
+impl FakeArrayTransport {
+    pub fn new(devices: impl IntoIterator<Item = (SocketAddrV4, MockDevice)>) -> Self {
+        Self {
+            devices: devices
+                .into_iter()
+                .map(|(addr, device)| (addr, Mutex::new(device)))
+                .collect(),
+        }
+    }
+
+    pub fn device(&self, addr: SocketAddrV4) -> Option<&Mutex<MockDevice>> {
+        self.devices
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, device)| device)
+    }
+}
+
+impl CtrlTransport for FakeArrayTransport {
+    fn exchange(
+        &self,
+        cmd: CtrlMsg,
+        remote: &[SocketAddrV4],
+        _timeout: Duration,
+    ) -> CmdReplies {
+        let mut replies = CmdReplies::default();
+        for &addr in remote {
+            let reply = self
+                .device(addr)
+                .and_then(|device| device.lock().unwrap().handle(&cmd));
+            if let Some(reply) = reply {
+                replies
+                    .normal
+                    .push((addr.into(), DeviceStatus::from_reply(&reply)));
+            }
+        }
+        replies
+    }
+}
+
+/// A [`MockDevice`] answering on a UDP socket, stopped on drop. The wire
+/// format is whatever `CtrlMsg::from_bytes` and `CtrlMsg::to_bytes` of
+/// sdaa_ctrl implement, they have to match what `send_cmd` puts on the wire.
+pub struct MockCtrlServer {
+    local_addr: SocketAddr,
+    tx_stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockCtrlServer {
+    pub fn spawn(bind_addr: SocketAddr, mut device: MockDevice) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let local_addr = socket.local_addr()?;
+        let (tx_stop, rx_stop) = bounded::<()>(1);
+        let thread = std::thread::spawn(move || {
+            let mut buf = [0_u8; 1500];
+            while let Err(TryRecvError::Empty) = rx_stop.try_recv() {
+                let Ok((n, src)) = socket.recv_from(&mut buf) else {
+                    continue;
+                };
+                let Some(msg) = CtrlMsg::from_bytes(&buf[..n]) else {
+                    eprintln!("ignoring malformed ctrl msg from {src}");
+                    continue;
+                };
+                if let Some(reply) = device.handle(&msg)
+                    && let Err(e) = socket.send_to(&reply.to_bytes(), src)
+                {
+                    eprintln!("failed to reply to {src}: {e}");
+                }
+            }
+        });
+        Ok(Self {
+            local_addr,
+            tx_stop: Some(tx_stop),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Serves until the thread ends, e.g. for a standalone binary.
+    pub fn join(mut self) {
+        if let Some(h) = self.thread.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+impl Drop for MockCtrlServer {
+    fn drop(&mut self) {
+        drop(self.tx_stop.take());
+        if let Some(h) = self.thread.take() {
+            let _ = h.join();
+        }
+    }
+}
@@ -1,4 +1,19 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 pub const N_PT_PER_FRAME: usize = 4096;
+pub const PAYLOAD_HEADER: u32 = 0x12345678;
+
+/// `Payload.version` values whose layout matches [`Payload`].
+pub const KNOWN_VERSIONS: &[u32] = &[0];
+
+/// Set in `Payload._reserved` by the receiver for frames it synthesised to
+/// fill a gap. The firmware leaves `_reserved` unused.
+pub const FLAG_GAP_FILLED: u64 = 1;
+
+/// Set by the receiver on the first frame after the stream broke, i.e. after
+/// `pkt_cnt` restarted or a recovery paused it. Samples before and after it
+/// are not contiguous.
+pub const FLAG_DISCONTINUITY: u64 = 2;
 
 #[repr(C)]
 pub struct Payload {
@@ -15,7 +30,7 @@ pub struct Payload {
 impl Default for Payload {
     fn default() -> Self {
         Self {
-            header: 0x12345678,
+            header: PAYLOAD_HEADER,
             version: 0,
             pkt_cnt: 0,
             base_id: 0,
@@ -36,4 +51,120 @@ impl Payload {
         self.port_id = rhs.port_id;
         self.npt_per_frame = rhs.npt_per_frame;
     }
+
+    pub fn is_gap_filled(&self) -> bool {
+        self._reserved & FLAG_GAP_FILLED != 0
+    }
+
+    pub fn set_gap_filled(&mut self, gap_filled: bool) {
+        if gap_filled {
+            self._reserved |= FLAG_GAP_FILLED;
+        } else {
+            self._reserved &= !FLAG_GAP_FILLED;
+        }
+    }
+
+    pub fn is_discontinuity(&self) -> bool {
+        self._reserved & FLAG_DISCONTINUITY != 0
+    }
+
+    pub fn set_discontinuity(&mut self, discontinuity: bool) {
+        if discontinuity {
+            self._reserved |= FLAG_DISCONTINUITY;
+        } else {
+            self._reserved &= !FLAG_DISCONTINUITY;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MalformedReason {
+    Length(usize),
+    Header(u32),
+    Version(u32),
+    FrameSize(u64),
+}
+
+#[derive(Debug, Default)]
+pub struct MalformedCounters {
+    pub length: AtomicU64,
+    pub header: AtomicU64,
+    pub version: AtomicU64,
+    pub frame_size: AtomicU64,
+}
+
+impl MalformedCounters {
+    pub fn record(&self, reason: MalformedReason) {
+        let counter = match reason {
+            MalformedReason::Length(_) => &self.length,
+            MalformedReason::Header(_) => &self.header,
+            MalformedReason::Version(_) => &self.version,
+            MalformedReason::FrameSize(_) => &self.frame_size,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.snapshot().total()
+    }
+
+    pub fn snapshot(&self) -> MalformedSnapshot {
+        MalformedSnapshot {
+            length: self.length.load(Ordering::Relaxed),
+            header: self.header.load(Ordering::Relaxed),
+            version: self.version.load(Ordering::Relaxed),
+            frame_size: self.frame_size.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Plain copy of [`MalformedCounters`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MalformedSnapshot {
+    pub length: u64,
+    pub header: u64,
+    pub version: u64,
+    pub frame_size: u64,
+}
+
+impl MalformedSnapshot {
+    pub fn total(&self) -> u64 {
+        self.length + self.header + self.version + self.frame_size
+    }
+}
+
+/// Checks received datagrams before they enter the pipeline. The default
+/// accepts any `version`, filtering on it is opt-in through [`new`](Self::new)
+/// since firmware stamping an unlisted version would otherwise deliver nothing.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadValidator {
+    versions: Option<Vec<u32>>,
+}
+
+impl PayloadValidator {
+    /// Accepts only `versions`, e.g. [`KNOWN_VERSIONS`].
+    pub fn new(versions: &[u32]) -> Self {
+        Self {
+            versions: Some(versions.to_vec()),
+        }
+    }
+
+    pub fn check(&self, payload: &Payload, nbytes: usize) -> Result<(), MalformedReason> {
+        if nbytes != std::mem::size_of::<Payload>() {
+            Err(MalformedReason::Length(nbytes))
+        } else if payload.header != PAYLOAD_HEADER {
+            Err(MalformedReason::Header(payload.header))
+        } else if self
+            .versions
+            .as_ref()
+            .is_some_and(|v| !v.contains(&payload.version))
+        {
+            Err(MalformedReason::Version(payload.version))
+        } else if payload.npt_per_frame != N_PT_PER_FRAME as u64 {
+            Err(MalformedReason::FrameSize(payload.npt_per_frame))
+        } else {
+            Ok(())
+        }
+    }
 }
@@ -1,12 +1,16 @@
+use std::fmt::Display;
+use std::io::ErrorKind;
 use std::net::SocketAddrV4;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use std::{
+    collections::VecDeque,
     net::{Ipv4Addr, UdpSocket},
-    ops::Deref,
-    sync::Arc,
+    ops::{Deref, DerefMut},
+    sync::{Arc, atomic::Ordering},
 };
 
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 use crossbeam::channel::{Receiver, Sender};
 use lockfree_object_pool::{LinearObjectPool, LinearOwnedReusable};
 use rustfft::FftPlanner;
@@ -14,15 +18,108 @@ use rustfft::num_complex::Complex;
 
 #[cfg(feature = "cuda")]
 use crate::ddc::DownConverter;
+#[cfg(feature = "cuda")]
+use crossbeam::channel::RecvTimeoutError;
 
 use crate::{
-    payload::{N_PT_PER_FRAME, Payload},
-    utils::as_mut_u8_slice,
+    arrival::ArrivalTracker,
+    error::SdaaError,
+    fill::{GapFill, GapFiller},
+    payload::{N_PT_PER_FRAME, Payload, PayloadValidator},
+    reorder::{Arrival, ReorderBuffer, Reordered},
+    siggen::{Pacing, SignalConfig, SignalGenerator},
+    sim::Pacer,
+    stats::RecvStats,
+    time_ref::TimeReference,
+    utils::{
+        as_mut_u8_slice, enable_rx_timestamps, join_source_specific_v4, leave_source_specific_v4,
+        recv_with_timestamp, set_multicast_all,
+    },
 };
 
+/// A multicast group joined by a [`MaybeMulticastReceiver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Membership {
+    pub group: Ipv4Addr,
+    /// Address of the interface to join on, see [`iface_addr`](crate::utils::iface_addr) to look it
+    /// up by name.
+    pub iface: Ipv4Addr,
+    /// Only datagrams from this sender, with an IGMPv3 source-specific join.
+    /// Any sender if `None`.
+    pub source: Option<Ipv4Addr>,
+}
+
+impl Membership {
+    pub fn any_source(group: Ipv4Addr, iface: Ipv4Addr) -> Self {
+        Self {
+            group,
+            iface,
+            source: None,
+        }
+    }
+
+    pub fn source_specific(group: Ipv4Addr, iface: Ipv4Addr, source: Ipv4Addr) -> Self {
+        Self {
+            group,
+            iface,
+            source: Some(source),
+        }
+    }
+}
+
+/// A group to join and optionally the only sender to take it from,
+/// written `group` or `group@source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupSource {
+    pub group: Ipv4Addr,
+    pub source: Option<Ipv4Addr>,
+}
+
+impl GroupSource {
+    /// Joined on `iface`, from `default_source` unless a source of its own is given.
+    pub fn membership(&self, iface: Ipv4Addr, default_source: Option<Ipv4Addr>) -> Membership {
+        Membership {
+            group: self.group,
+            iface,
+            source: self.source.or(default_source),
+        }
+    }
+}
+
+impl FromStr for GroupSource {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, source) = match s.split_once('@') {
+            Some((group, source)) => (group, Some(source)),
+            None => (s, None),
+        };
+        let group: Ipv4Addr = group
+            .parse()
+            .map_err(|e| format!("invalid group {group}: {e}"))?;
+        if !group.is_multicast() {
+            return Err(format!("{group} is not a multicast group"));
+        }
+        let source = source
+            .map(|source| source.parse().map_err(|e| format!("invalid source {source}: {e}")))
+            .transpose()?;
+        Ok(Self { group, source })
+    }
+}
+
+impl Display for Membership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "group {}", self.group)?;
+        if let Some(source) = self.source {
+            write!(f, " from {source}")?;
+        }
+        write!(f, " on interface {}", self.iface)
+    }
+}
+
 pub struct MaybeMulticastReceiver {
     socket: UdpSocket,
-    group_and_iface: Option<(Ipv4Addr, Ipv4Addr)>, // (group, iface)
+    memberships: Vec<Membership>,
+    timestamps: bool,
 }
 
 impl MaybeMulticastReceiver {
@@ -30,24 +127,58 @@ impl MaybeMulticastReceiver {
         bind_addr: SocketAddrV4,
         group_and_iface: Option<(Ipv4Addr, Ipv4Addr)>,
     ) -> std::io::Result<Self> {
-        let socket = UdpSocket::bind(bind_addr)?;
+        let memberships: Vec<Membership> = group_and_iface
+            .map(|(group, iface)| Membership::any_source(group, iface))
+            .into_iter()
+            .collect();
+        Self::with_memberships(bind_addr, &memberships)
+    }
 
-        if let Some((group, iface)) = group_and_iface {
-            socket.join_multicast_v4(&group, &iface)?;
+    /// Binds `bind_addr` and joins all of `memberships`. To receive several
+    /// groups on one socket, bind the unspecified address.
+    ///
+    /// Everything joined ends up in one stream, so all groups have to carry
+    /// the packets of the same board, e.g. over redundant paths. The frames
+    /// of several boards would interleave their `pkt_cnt` and be taken for
+    /// duplicates and restarts, those need a receiver each.
+    pub fn with_memberships(
+        bind_addr: SocketAddrV4,
+        memberships: &[Membership],
+    ) -> std::io::Result<Self> {
+        let mut receiver = Self::from(UdpSocket::bind(bind_addr)?);
+        for &m in memberships {
+            receiver.join(m)?;
         }
+        Ok(receiver)
+    }
 
-        Ok(Self {
-            socket,
-            group_and_iface,
-        })
+    /// Joins another group, left again on drop.
+    pub fn join(&mut self, m: Membership) -> std::io::Result<()> {
+        if self.memberships.is_empty() {
+            // groups joined by other programs on the same port are not ours
+            set_multicast_all(&self.socket, false)?;
+        }
+        match m.source {
+            None => self.socket.join_multicast_v4(&m.group, &m.iface)?,
+            Some(source) => join_source_specific_v4(&self.socket, m.group, m.iface, source)?,
+        }
+        self.memberships.push(m);
+        Ok(())
+    }
+
+    pub fn memberships(&self) -> &[Membership] {
+        &self.memberships
     }
 }
 
 impl Drop for MaybeMulticastReceiver {
     fn drop(&mut self) {
-        if let Some((group, iface)) = self.group_and_iface {
-            let _ = self.socket.leave_multicast_v4(&group, &iface);
-            println!("Left multicast group {} on interface {}", group, iface);
+        for m in &self.memberships {
+            let _ = match m.source {
+                None => self.socket.leave_multicast_v4(&m.group, &m.iface),
+                Some(source) => leave_source_specific_v4(&self.socket, m.group, m.iface, source),
+            };
+            println!("Left multicast {m}");
         }
     }
 }
@@ -63,16 +194,196 @@ impl From<UdpSocket> for MaybeMulticastReceiver {
     fn from(socket: UdpSocket) -> Self {
         Self {
             socket,
-            group_and_iface: None,
+            memberships: Vec::new(),
+            timestamps: false,
+        }
+    }
+}
+
+/// Where `recv_pkt_from` gets its datagrams. Besides the sockets here,
+/// [`source`](crate::source) has file, pcap and generator sources.
+pub trait PacketSource {
+    /// Reads one datagram into `buf`. Should fail after about a second
+    /// without traffic, and with [`ErrorKind::UnexpectedEof`] once nothing
+    /// more will come, which ends the stream.
+    ///
+    /// [`ErrorKind::UnexpectedEof`]: std::io::ErrorKind::UnexpectedEof
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize>;
+
+    /// Asks for kernel receive times on the following datagrams.
+    fn enable_timestamps(&mut self) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    /// Same as [`recv_into`](Self::recv_into), also returning the receive
+    /// time if timestamps are enabled.
+    fn recv_timestamped(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, Option<DateTime<Utc>>)> {
+        self.recv_into(buf).map(|s| (s, None))
+    }
+}
+
+impl PacketSource for MaybeMulticastReceiver {
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.socket.recv_from(buf).map(|(s, _a)| s)
+    }
+
+    fn enable_timestamps(&mut self) -> std::io::Result<()> {
+        enable_rx_timestamps(&self.socket)?;
+        self.timestamps = true;
+        Ok(())
+    }
+
+    fn recv_timestamped(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, Option<DateTime<Utc>>)> {
+        if self.timestamps {
+            recv_with_timestamp(&self.socket, buf)
+        } else {
+            self.recv_into(buf).map(|s| (s, None))
         }
     }
 }
 
 pub enum RecvCmd {
     Destroy,
+    /// Drop everything received until [`RecvCmd::Resume`], e.g. while the
+    /// device is brought up again.
+    Pause,
+    /// Forward packets again, the first one is marked as a discontinuity.
+    Resume,
+}
+
+/// What `recv_pkt` was told by [`RecvCmd::Pause`] and [`RecvCmd::Resume`].
+#[derive(Default)]
+struct FlowState {
+    paused: bool,
+    /// The next forwarded frame follows a break.
+    discontinuity: bool,
+    /// Paused since the sequencing state was last reset.
+    reset: bool,
+}
+
+impl FlowState {
+    /// Returns false on [`RecvCmd::Destroy`].
+    fn apply(&mut self, cmd: RecvCmd) -> bool {
+        match cmd {
+            RecvCmd::Destroy => return false,
+            RecvCmd::Pause => {
+                self.paused = true;
+                self.reset = true;
+            }
+            RecvCmd::Resume => {
+                if self.paused {
+                    self.paused = false;
+                    self.discontinuity = true;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecvConfig {
+    pub validator: PayloadValidator,
+    /// Number of frames held back to put late packets into their slot
+    /// before a missing one is filled in.
+    pub reorder_depth: usize,
+    pub gap_fill: GapFill,
+    /// Take kernel receive timestamps and fill in `RecvStats::arrival`.
+    pub timestamps: bool,
+    /// Packets arriving this much later than the frame period predicts count
+    /// as a host stall.
+    pub stall_threshold: Duration,
+}
+
+impl Default for RecvConfig {
+    fn default() -> Self {
+        Self {
+            validator: PayloadValidator::default(),
+            reorder_depth: 16,
+            gap_fill: GapFill::default(),
+            timestamps: false,
+            stall_threshold: Duration::from_millis(2),
+        }
+    }
+}
+
+/// Output of a processing stage together with how much of the input behind
+/// it was actually received. Both counts are in raw ADC samples, so gap
+/// filled frames show up as `n_valid < n_total`.
+pub struct Block<T> {
+    pub data: Vec<T>,
+    pub n_valid: usize,
+    pub n_total: usize,
+    /// Raw sample index (`pkt_cnt * N_PT_PER_FRAME` plus offset) of the first
+    /// input sample, to be converted with a [`TimeReference`].
+    pub first_sample: u64,
+    /// Some input frame was marked with [`FLAG_DISCONTINUITY`](crate::payload::FLAG_DISCONTINUITY),
+    /// `first_sample` of the following blocks restarts.
+    pub discontinuity: bool,
 }
 
-pub fn fake_dev(tx_payload: Sender<LinearOwnedReusable<Payload>>, rx_cmd: Receiver<RecvCmd>) {
+impl<T> Block<T> {
+    pub fn new(data: Vec<T>) -> Self {
+        Self {
+            data,
+            n_valid: 0,
+            n_total: 0,
+            first_sample: 0,
+            discontinuity: false,
+        }
+    }
+
+    pub fn start_time(&self, time_ref: &TimeReference) -> DateTime<Utc> {
+        time_ref.sample_time(self.first_sample)
+    }
+
+    pub fn valid_fraction(&self) -> f64 {
+        if self.n_total == 0 {
+            0.0
+        } else {
+            self.n_valid as f64 / self.n_total as f64
+        }
+    }
+}
+
+impl<T> Deref for Block<T> {
+    type Target = Vec<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Block<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+/// Number of received samples in a frame.
+fn n_valid_in(payload: &Payload) -> usize {
+    if payload.is_gap_filled() {
+        0
+    } else {
+        N_PT_PER_FRAME
+    }
+}
+
+/// Stands in for `recv_pkt` without hardware, feeding frames synthesized
+/// from `signal` at the given pace.
+pub fn fake_dev(
+    tx_payload: Sender<LinearOwnedReusable<Payload>>,
+    rx_cmd: Receiver<RecvCmd>,
+    signal: SignalConfig,
+    pacing: Pacing,
+) {
+    let mut generator = SignalGenerator::new(signal);
+    let mut pacer = Pacer::with_pacing(pacing);
     let mut last_print_time = Instant::now();
     let t0 = Instant::now();
     let print_interval = Duration::from_secs(2);
@@ -84,6 +395,7 @@ pub fn fake_dev(tx_payload: Sender<LinearOwnedReusable<Payload>>, rx_cmd: Receiv
         },
         |v| {
             v.pkt_cnt = 0;
+            v._reserved = 0;
             v.data.fill(0);
         },
     ));
@@ -92,11 +404,17 @@ pub fn fake_dev(tx_payload: Sender<LinearOwnedReusable<Payload>>, rx_cmd: Receiv
         if !rx_cmd.is_empty() {
             match rx_cmd.recv().expect("failed to recv cmd") {
                 RecvCmd::Destroy => break,
+                // nothing to recover here
+                RecvCmd::Pause | RecvCmd::Resume => {}
             }
         }
         let mut payload = pool.pull_owned();
         payload.pkt_cnt = pkt_cnt;
+        if !generator.is_silent() {
+            generator.fill(&mut payload.data);
+        }
 
+        pacer.wait_frame(pkt_cnt);
         let now = Instant::now();
 
         if payload.pkt_cnt == 0 {
@@ -122,15 +440,57 @@ pub fn fake_dev(tx_payload: Sender<LinearOwnedReusable<Payload>>, rx_cmd: Receiv
 
 pub fn recv_pkt(
     socket: MaybeMulticastReceiver,
+    config: RecvConfig,
+    stats: Arc<RecvStats>,
     tx_payload: Sender<LinearOwnedReusable<Payload>>,
     rx_cmd: Receiver<RecvCmd>,
-) {
-    let mut last_print_time = Instant::now();
-    let print_interval = Duration::from_secs(2);
+) -> Result<(), SdaaError> {
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    recv_pkt_from(socket, config, stats, tx_payload, rx_cmd);
+    Ok(())
+}
+
+/// Blocks while `tx` is full. Returns false once the stage should stop,
+/// a pause arriving meanwhile drops `item`.
+fn send_or_stall<T>(
+    tx: &Sender<T>,
+    item: T,
+    rx_cmd: &Receiver<RecvCmd>,
+    stats: &RecvStats,
+    flow: &mut FlowState,
+) -> bool {
+    let mut stalled = false;
+    while tx.is_full() {
+        //eprint!("O");
+        if !stalled {
+            stats.queue_full.fetch_add(1, Ordering::Relaxed);
+            stalled = true;
+        }
+        if !rx_cmd.is_empty() {
+            if !flow.apply(rx_cmd.recv().expect("failed to recv cmd")) {
+                return false;
+            }
+            if flow.paused {
+                return true;
+            }
+        }
+    }
+    tx.send(item).is_ok()
+}
 
-    let mut next_cnt = None;
-    let mut ndropped = 0;
-    let mut nreceived = 0;
+/// Same as [`recv_pkt`], but reads datagrams from any [`PacketSource`].
+/// The source is expected to return an error after about a second without
+/// traffic so that `rx_cmd` is polled regularly. Datagrams rejected by
+/// `config.validator` are counted in `stats.malformed` and dropped before
+/// sequencing. Returns once the source reports the end of the stream,
+/// after forwarding what is held back.
+pub fn recv_pkt_from<S: PacketSource>(
+    mut source: S,
+    config: RecvConfig,
+    stats: Arc<RecvStats>,
+    tx_payload: Sender<LinearOwnedReusable<Payload>>,
+    rx_cmd: Receiver<RecvCmd>,
+) {
     let pool: Arc<LinearObjectPool<Payload>> = Arc::new(LinearObjectPool::new(
         move || {
             //eprint!("o");
@@ -138,114 +498,151 @@ pub fn recv_pkt(
         },
         |v| {
             v.pkt_cnt = 0;
+            v._reserved = 0;
             v.data.fill(0);
         },
     ));
-    //socket.set_nonblocking(true).unwrap();
-    socket
-        .set_read_timeout(Some(Duration::from_secs(1)))
-        .expect("failed to set timeout");
-    loop {
-        if !rx_cmd.is_empty() {
-            match rx_cmd.recv().expect("failed to recv cmd") {
-                RecvCmd::Destroy => break,
+    let mut reorder = ReorderBuffer::new(config.reorder_depth);
+    let mut ready = VecDeque::with_capacity(reorder.depth());
+    let mut template = Box::<Payload>::default();
+    let mut filler = GapFiller::new(config.gap_fill);
+    let mut tracker = ArrivalTracker::new(config.stall_threshold);
+    let timestamps = config.timestamps
+        && match source.enable_timestamps() {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("receive timestamps not available: {e}");
+                false
             }
+        };
+    let mut burst = 0;
+    let mut flow = FlowState::default();
+    let mut ended = false;
+    //socket.set_nonblocking(true).unwrap();
+    while !ended {
+        if !rx_cmd.is_empty() && !flow.apply(rx_cmd.recv().expect("failed to recv cmd")) {
+            break;
+        }
+        // also after a pause taken while stalled in `send_or_stall`
+        if flow.reset {
+            // start over once resumed, nothing held back is worth waiting for
+            flow.reset = false;
+            reorder = ReorderBuffer::new(config.reorder_depth);
+            ready.clear();
+            tracker.reset();
         }
         let mut payload = pool.pull_owned();
         let buf = as_mut_u8_slice(&mut payload as &mut Payload);
-        match socket.recv_from(buf) {
-            Ok((s, _a)) => {
-                if s != std::mem::size_of::<Payload>() {
+        match source.recv_timestamped(buf) {
+            Ok(_) if flow.paused => continue,
+            Ok((s, stamp)) => {
+                if let Err(reason) = config.validator.check(&payload, s) {
+                    stats.malformed.record(reason);
                     continue;
                 }
-            }
-            _ => continue,
-        }
-
-        let now = Instant::now();
-
-        if now.duration_since(last_print_time) >= print_interval {
-            let local_time = Local::now().format("%Y-%m-%d %H:%M:%S");
-            println!(
-                "{local_time} {ndropped} pkts dropped q={} ratio<{:e}",
-                tx_payload.len(),
-                (1 + ndropped) as f64 / nreceived as f64
-            );
-            last_print_time = now;
-        }
-
-        if next_cnt.is_none() {
-            next_cnt = Some(payload.pkt_cnt);
-            ndropped = 0;
-        }
-
-        if payload.pkt_cnt == 0 {
-            ndropped = 0;
-            nreceived = 0;
-            let local_time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-            println!();
-            println!("==================================");
-            println!("start time:{local_time}");
-            println!("==================================");
-        }
+                stats.payload_version.get_or_init(|| payload.version);
+                payload.set_gap_filled(false);
+                payload.set_discontinuity(false);
+
+                if payload.pkt_cnt == 0 {
+                    let local_time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+                    println!();
+                    println!("==================================");
+                    println!("start time:{local_time}");
+                    println!("==================================");
+                }
 
-        while let Some(ref mut c) = next_cnt {
-            //let current_cnt = c + 1;
-            if *c >= payload.pkt_cnt {
-                //actually = is sufficient.
-                *c = payload.pkt_cnt + 1;
-                if tx_payload.is_full() {
-                    //eprint!("O");
-                    if !rx_cmd.is_empty() {
-                        match rx_cmd.recv().expect("failed to recv cmd") {
-                            RecvCmd::Destroy => return,
+                template.copy_header(&payload);
+                filler.observe(&payload);
+                let pkt_cnt = payload.pkt_cnt;
+                match reorder.push(pkt_cnt, payload, &mut ready) {
+                    arrival @ (Arrival::Accepted | Arrival::Restart { .. }) => {
+                        stats.received.fetch_add(1, Ordering::Relaxed);
+                        if let Arrival::Restart { from } = arrival {
+                            stats.restarts.fetch_add(1, Ordering::Relaxed);
+                            if from != pkt_cnt {
+                                // the packet that started it was counted as late
+                                stats.late.fetch_sub(1, Ordering::Relaxed);
+                                stats.received.fetch_add(1, Ordering::Relaxed);
+                            }
+                            // the first frame of the new sequence is already released
+                            if let Some(p) = ready.iter_mut().rev().find_map(|r| match r {
+                                Reordered::Packet(p) if p.pkt_cnt == from => Some(p),
+                                _ => None,
+                            }) {
+                                p.set_discontinuity(true);
+                            }
+                        }
+                        if timestamps && let Some(t) = stamp {
+                            if matches!(arrival, Arrival::Restart { .. }) {
+                                tracker.reset();
+                            }
+                            tracker.observe(pkt_cnt, t);
+                            if pkt_cnt.is_multiple_of(1024) {
+                                *stats.arrival.lock().unwrap() = Some(tracker.summary());
+                            }
                         }
                     }
-                    continue;
-                }
-                nreceived += 1;
-                if let Ok(()) = tx_payload.send(payload) {
-                    break;
-                } else {
-                    return;
+                    Arrival::Late => {
+                        stats.late.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Arrival::Duplicate => {
+                        stats.duplicate.fetch_add(1, Ordering::Relaxed);
+                    }
                 }
             }
+            // no traffic, release what is held back instead of waiting for the window
+            Err(e) => {
+                ended = e.kind() == ErrorKind::UnexpectedEof;
+                reorder.flush(&mut ready);
+            }
+        }
 
-            ndropped += 1;
-
-            let mut payload1 = pool.pull_owned();
-            payload1.copy_header(&payload);
-            payload1.pkt_cnt = *c;
-            if tx_payload.is_full() {
-                //eprint!("O");
-                if !rx_cmd.is_empty() {
-                    match rx_cmd.recv().expect("failed to recv cmd") {
-                        RecvCmd::Destroy => return,
+        while let Some(item) = ready.pop_front() {
+            let mut payload = match item {
+                Reordered::Packet(payload) => {
+                    stats.record_burst(burst);
+                    burst = 0;
+                    payload
+                }
+                Reordered::Gap(pkt_cnt) => {
+                    burst += 1;
+                    stats.gap_filled.fetch_add(1, Ordering::Relaxed);
+                    let mut payload1 = pool.pull_owned();
+                    if !filler.fill(&mut payload1) {
+                        continue;
                     }
+                    payload1.copy_header(&template);
+                    payload1.pkt_cnt = pkt_cnt;
+                    payload1.set_gap_filled(true);
+                    payload1
                 }
-                continue;
+            };
+            if flow.discontinuity {
+                payload.set_discontinuity(true);
+                flow.discontinuity = false;
             }
-            nreceived += 1;
-            if tx_payload.send(payload1).is_err() {
+            if !send_or_stall(&tx_payload, payload, &rx_cmd, &stats, &mut flow) {
                 return;
             }
-
-            *c += 1;
+            if flow.paused {
+                ready.clear();
+            }
         }
     }
 }
 
 pub fn pkt_fft(
     rx: Receiver<LinearOwnedReusable<Payload>>,
-    tx: Sender<LinearOwnedReusable<Vec<Complex<f32>>>>,
+    tx: Sender<LinearOwnedReusable<Block<Complex<f32>>>>,
     nch: usize,
 ) {
     assert!(N_PT_PER_FRAME % (nch * 2) == 0 || (nch * 2) % N_PT_PER_FRAME == 0);
     let nbuf = (nch * 2).max(N_PT_PER_FRAME);
-    let pool: Arc<LinearObjectPool<Vec<Complex<f32>>>> = Arc::new(LinearObjectPool::new(
+    let pool: Arc<LinearObjectPool<Block<Complex<f32>>>> = Arc::new(LinearObjectPool::new(
         move || {
             //eprint!(".");
-            vec![Complex::default(); nbuf / 2]
+            Block::new(vec![Complex::default(); nbuf / 2])
         },
         |_v| {},
     ));
@@ -255,7 +652,14 @@ pub fn pkt_fft(
 
     let mut buffer = vec![Complex::<f32>::default(); nbuf];
     let mut offset = 0;
+    let mut n_valid = 0;
+    let mut first_sample = 0;
+    let mut discontinuity = false;
     while let Ok(payload) = rx.recv() {
+        if offset == 0 {
+            first_sample = payload.pkt_cnt * N_PT_PER_FRAME as u64;
+        }
+        discontinuity |= payload.is_discontinuity();
         buffer[offset..(offset + N_PT_PER_FRAME)]
             .iter_mut()
             .zip(payload.data.iter())
@@ -264,6 +668,7 @@ pub fn pkt_fft(
                 //*a=(b as f32).into();
             });
         offset += N_PT_PER_FRAME;
+        n_valid += n_valid_in(&payload);
         if offset == nbuf {
             offset = 0;
             fft.process(&mut buffer);
@@ -275,6 +680,12 @@ pub fn pkt_fft(
                 .for_each(|(a, b)| {
                     b.copy_from_slice(a);
                 });
+            result.n_valid = n_valid;
+            result.n_total = nbuf;
+            result.first_sample = first_sample;
+            result.discontinuity = discontinuity;
+            n_valid = 0;
+            discontinuity = false;
 
             //tx.try_send(result).unwrap();
             if tx.send(result).is_err() {
@@ -284,29 +695,77 @@ pub fn pkt_fft(
     }
 }
 
+/// Adds `len` samples starting at `start` of a waterfall batch to the
+/// per-row valid counts.
+#[cfg(feature = "cuda")]
+fn add_valid_rows(row_valid: &mut [usize], row_len: usize, start: usize, len: usize) {
+    let mut pos = start;
+    while pos < start + len {
+        let row = pos / row_len;
+        let n = (row_len - pos % row_len).min(start + len - pos);
+        row_valid[row] += n;
+        pos += n;
+    }
+}
+
 #[cfg(feature = "cuda")]
 pub fn pkt_wf(
     rx: Receiver<LinearOwnedReusable<Payload>>,
-    tx: Sender<LinearOwnedReusable<Vec<f32>>>,
+    tx: Sender<LinearOwnedReusable<Block<f32>>>,
     nch: usize,
     nbatch: usize,
     nint: usize,
-) {
-    assert_eq!(nbatch % nint, 0);
+) -> Result<(), SdaaError> {
     use crate::cuwf::WfResource;
-    let mut wf = WfResource::new(nch, nbatch, nint);
+    let mut wf = WfResource::new(nch, nbatch, nint)?;
     let nbuf = nch * nbatch / nint;
+    let nrow = nbatch / nint;
+    let row_len = 2 * nch * nint;
+    let batch_len = 2 * nch * nbatch;
 
-    let pool: Arc<LinearObjectPool<Vec<f32>>> = Arc::new(LinearObjectPool::new(
+    let pool: Arc<LinearObjectPool<Block<f32>>> = Arc::new(LinearObjectPool::new(
         move || {
             //eprint!(".");
-            vec![0_f32; nbuf]
+            Block::new(vec![0_f32; nbuf])
         },
         |_v| {},
     ));
     let mut result = pool.pull_owned();
+    let mut row_valid = vec![0; nrow];
+    let mut pos = 0;
+    let mut batch_start = None;
+    let mut discontinuity = false;
     while let Ok(payload) = rx.recv() {
+        let valid = !payload.is_gap_filled();
+        discontinuity |= payload.is_discontinuity();
+        let frame_start = payload.pkt_cnt * N_PT_PER_FRAME as u64;
+        let first_sample = *batch_start.get_or_insert(frame_start);
         if wf.process(&payload.data, result.as_mut_slice()) {
+            // the batch ends inside this frame, the rest of it starts the next batch
+            let head = batch_len - pos;
+            if valid {
+                add_valid_rows(&mut row_valid, row_len, pos, head);
+            }
+            // rescale rows that contain filled frames as if they were fully received
+            for (row, &n) in result.chunks_mut(nch).zip(row_valid.iter()) {
+                if n > 0 && n < row_len {
+                    let scale = row_len as f32 / n as f32;
+                    row.iter_mut().for_each(|x| *x *= scale);
+                }
+            }
+            result.n_valid = row_valid.iter().sum();
+            result.n_total = batch_len;
+            result.first_sample = first_sample;
+            result.discontinuity = discontinuity;
+
+            discontinuity = false;
+            row_valid.fill(0);
+            pos = N_PT_PER_FRAME - head;
+            batch_start = Some(frame_start + head as u64);
+            if valid {
+                add_valid_rows(&mut row_valid, row_len, 0, pos);
+            }
+
             if tx.is_full() {
                 eprintln!("waterfall channel full, discarding");
                 continue;
@@ -315,23 +774,35 @@ pub fn pkt_wf(
                 break;
             }
             result = pool.pull_owned();
+        } else {
+            if valid {
+                add_valid_rows(&mut row_valid, row_len, pos, N_PT_PER_FRAME);
+            }
+            pos += N_PT_PER_FRAME;
         }
     }
+    Ok(())
 }
 
+/// Sums `nint` power spectra. Spectra computed from gap filled frames are
+/// left out and the sum is scaled up to what `nint` received spectra would
+/// give; `n_valid` of the output tells how much data it is based on.
 pub fn pkt_integrate(
-    rx: Receiver<LinearOwnedReusable<Vec<Complex<f32>>>>,
-    tx: Sender<LinearOwnedReusable<Vec<f32>>>,
+    rx: Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+    tx: Sender<LinearOwnedReusable<Block<f32>>>,
     nch: usize,
     nint: usize,
 ) {
-    let pool: Arc<LinearObjectPool<Vec<f32>>> = Arc::new(LinearObjectPool::new(
+    let pool: Arc<LinearObjectPool<Block<f32>>> = Arc::new(LinearObjectPool::new(
         move || {
             //eprint!(".");
-            vec![0.0; nch]
+            Block::new(vec![0.0; nch])
         },
         |v| {
             v.fill(0.0);
+            v.n_valid = 0;
+            v.n_total = 0;
+            v.discontinuity = false;
         },
     ));
 
@@ -340,13 +811,26 @@ pub fn pkt_integrate(
     while let Ok(x) = rx.recv() {
         let n = x.len();
         assert!(n % nch == 0);
-        for x1 in x.chunks(nch) {
-            result.iter_mut().zip(x1).for_each(|(a, b)| {
-                *a += b.norm_sqr();
-            });
+        let nspec = n / nch;
+        for (i, x1) in x.chunks(nch).enumerate() {
+            if add_cnt == 0 {
+                result.first_sample = x.first_sample + (i * x.n_total / nspec) as u64;
+            }
+            if x.n_valid > 0 {
+                result.iter_mut().zip(x1).for_each(|(a, b)| {
+                    *a += b.norm_sqr();
+                });
+            }
+            result.n_valid += x.n_valid / nspec;
+            result.n_total += x.n_total / nspec;
+            result.discontinuity |= x.discontinuity;
             add_cnt += 1;
             if add_cnt == nint {
                 add_cnt = 0;
+                if result.n_valid > 0 && result.n_valid < result.n_total {
+                    let scale = result.n_total as f32 / result.n_valid as f32;
+                    result.iter_mut().for_each(|a| *a *= scale);
+                }
                 if tx.send(result).is_err() {
                     return;
                 }
@@ -365,26 +849,41 @@ pub enum DdcCmd {
 #[cfg(feature = "cuda")]
 pub fn pkt_ddc(
     rx: Receiver<LinearOwnedReusable<Payload>>,
-    tx: Sender<LinearOwnedReusable<Vec<Complex<f32>>>>,
-    ndec: usize,
+    tx: Sender<LinearOwnedReusable<Block<Complex<f32>>>>,
+    ddc: DownConverter,
     rx_ddc_cmd: Receiver<DdcCmd>,
     tx_recv_cmd: Sender<RecvCmd>,
-    fir_coeffs: &[f32],
-) {
-    let mut ddc = DownConverter::new(ndec, fir_coeffs);
+) -> Result<(), SdaaError> {
+    let result = ddc_loop(rx, tx, ddc, rx_ddc_cmd);
+    // the receiver has nowhere to send to anymore, whatever the reason
+    let _ = tx_recv_cmd.send(RecvCmd::Destroy);
+    result
+}
+
+#[cfg(feature = "cuda")]
+fn ddc_loop(
+    rx: Receiver<LinearOwnedReusable<Payload>>,
+    tx: Sender<LinearOwnedReusable<Block<Complex<f32>>>>,
+    mut ddc: DownConverter,
+    rx_ddc_cmd: Receiver<DdcCmd>,
+) -> Result<(), SdaaError> {
     let n_out_data = ddc.n_out_data();
-    let pool: Arc<LinearObjectPool<Vec<Complex<f32>>>> = Arc::new(LinearObjectPool::new(
+    let pool: Arc<LinearObjectPool<Block<Complex<f32>>>> = Arc::new(LinearObjectPool::new(
         move || {
             //eprint!(".");
-            vec![Complex::<f32>::default(); n_out_data]
+            Block::new(vec![Complex::<f32>::default(); n_out_data])
         },
         |_v| {},
     ));
-
-    let mut lo_ch = if let DdcCmd::LoCh(c) = rx_ddc_cmd.recv().expect("failed to recv cmd") {
-        c
-    } else {
-        N_PT_PER_FRAME as isize / 4
+    let mut n_valid = 0;
+    let mut n_total = 0;
+    let mut first_sample = 0;
+    let mut discontinuity = false;
+
+    let mut lo_ch = match rx_ddc_cmd.recv() {
+        Ok(DdcCmd::LoCh(c)) => c,
+        Ok(DdcCmd::Destroy) => N_PT_PER_FRAME as isize / 4,
+        Err(_) => return Err(SdaaError::ChannelClosed),
     };
 
     loop {
@@ -402,11 +901,27 @@ pub fn pkt_ddc(
                 break;
             }
         }
-        if let Ok(payload) = rx.recv_timeout(Duration::from_secs(1))
-            && ddc.ddc(&payload.data, lo_ch)
-        {
+        let payload = match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(payload) => payload,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if n_total == 0 {
+            first_sample = payload.pkt_cnt * N_PT_PER_FRAME as u64;
+        }
+        n_valid += n_valid_in(&payload);
+        n_total += N_PT_PER_FRAME;
+        discontinuity |= payload.is_discontinuity();
+        if ddc.ddc(&payload.data, lo_ch)? {
             let mut outdata = pool.pull_owned();
             ddc.fetch_output(&mut outdata);
+            outdata.n_valid = n_valid;
+            outdata.n_total = n_total;
+            outdata.first_sample = first_sample;
+            outdata.discontinuity = discontinuity;
+            n_valid = 0;
+            n_total = 0;
+            discontinuity = false;
 
             if tx.is_full() {
                 eprintln!("ddc channel full, discarding");
@@ -417,10 +932,7 @@ pub fn pkt_ddc(
             }
         }
     }
-    drop(rx);
-    tx_recv_cmd
-        .send(RecvCmd::Destroy)
-        .expect("failed to send cmd");
+    Ok(())
 }
 
 /*
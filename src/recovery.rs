@@ -0,0 +1,183 @@
+use std::{
+    sync::{Arc, Mutex, atomic::Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use crossbeam::{
+    channel::{Receiver, Sender, bounded, never},
+    select,
+};
+
+use crate::{
+    health::{DeviceEvent, DeviceEventKind},
+    pipeline::RecvCmd,
+    sdr::{DeviceError, LockPolicy, SdrCtrl},
+    stats::RecvStats,
+    transport::CtrlTransport,
+};
+
+/// When and how [`Recovery`] brings the device back up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryPolicy {
+    /// Recover on [`DeviceEventKind::LockLost`].
+    pub on_lock_loss: bool,
+    /// Recover when `pkt_cnt` restarts, see [`RecvStats::restarts`]. Off by
+    /// default, a restart the device did on its own is already marked in the
+    /// stream, while recovering re-syncs it and breaks the recording again.
+    pub on_restart: bool,
+    pub lock: LockPolicy,
+    /// Bring-up attempts before giving up until the next trigger.
+    pub max_attempts: usize,
+    /// Wait between failed attempts.
+    pub backoff: Duration,
+    /// How often the receive counters are checked for restarts.
+    pub poll: Duration,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            on_lock_loss: true,
+            on_restart: false,
+            lock: LockPolicy::default(),
+            max_attempts: 3,
+            backoff: Duration::from_secs(5),
+            poll: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryTrigger {
+    LockLost,
+    Restart,
+}
+
+/// One run of the recovery sequence.
+#[derive(Debug, Clone)]
+pub struct RecoveryRecord {
+    pub trigger: RecoveryTrigger,
+    pub started: DateTime<Utc>,
+    pub duration: Duration,
+    pub attempts: usize,
+    /// Error of the last attempt if all of them failed.
+    pub result: Result<(), DeviceError>,
+}
+
+/// Watches for lock loss and stream restarts and runs the bring-up sequence
+/// again. The receiver is paused meanwhile and marks the first frame after
+/// it with [`FLAG_DISCONTINUITY`](crate::payload::FLAG_DISCONTINUITY).
+pub struct Recovery {
+    history: Arc<Mutex<Vec<RecoveryRecord>>>,
+    tx_stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Recovery {
+    /// `events` should come from a [`HealthMonitor`](crate::health::HealthMonitor)
+    /// on the same device, lock loss goes unnoticed otherwise.
+    pub fn spawn<T: CtrlTransport + Send + Sync + 'static>(
+        ctrl: Arc<SdrCtrl<T>>,
+        stats: Arc<RecvStats>,
+        tx_recv_cmd: Sender<RecvCmd>,
+        events: Receiver<DeviceEvent>,
+        policy: RecoveryPolicy,
+    ) -> Self {
+        let history = Arc::new(Mutex::new(Vec::new()));
+        let (tx_stop, rx_stop) = bounded::<()>(1);
+        let history1 = Arc::clone(&history);
+        let thread = std::thread::spawn(move || {
+            let mut events = events;
+            let mut seen = stats.restarts.load(Ordering::Relaxed);
+            loop {
+                let mut trigger = select! {
+                    recv(rx_stop) -> _ => break,
+                    recv(events) -> event => match event {
+                        Ok(e) if e.kind == DeviceEventKind::LockLost && policy.on_lock_loss => {
+                            Some(RecoveryTrigger::LockLost)
+                        }
+                        Ok(_) => None,
+                        Err(_) => {
+                            events = never();
+                            None
+                        }
+                    },
+                    default(policy.poll) => None,
+                };
+                let restarts = stats.restarts.load(Ordering::Relaxed);
+                if restarts > seen && policy.on_restart {
+                    trigger = trigger.or(Some(RecoveryTrigger::Restart));
+                }
+                seen = restarts;
+                let Some(trigger) = trigger else {
+                    continue;
+                };
+
+                let record = recover(&ctrl, &tx_recv_cmd, &policy, trigger);
+                if let Err(ref e) = record.result {
+                    eprintln!("recovery failed: {e}");
+                }
+                history1.lock().unwrap().push(record);
+                // whatever happened during the recovery is stale now
+                while events.try_recv().is_ok() {}
+                seen = stats.restarts.load(Ordering::Relaxed);
+            }
+        });
+        Self {
+            history,
+            tx_stop: Some(tx_stop),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn history(&self) -> Vec<RecoveryRecord> {
+        self.history.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Recovery {
+    fn drop(&mut self) {
+        drop(self.tx_stop.take());
+        if let Some(h) = self.thread.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+fn recover<T: CtrlTransport>(
+    ctrl: &SdrCtrl<T>,
+    tx_recv_cmd: &Sender<RecvCmd>,
+    policy: &RecoveryPolicy,
+    trigger: RecoveryTrigger,
+) -> RecoveryRecord {
+    eprintln!("recovering after {trigger:?}");
+    let started = Utc::now();
+    let t0 = Instant::now();
+    let _ = tx_recv_cmd.send(RecvCmd::Pause);
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempts = 0;
+    let mut result = Ok(());
+    while attempts < max_attempts {
+        if attempts > 0 {
+            std::thread::sleep(policy.backoff);
+        }
+        attempts += 1;
+        result = ctrl
+            .bring_up(&policy.lock)
+            .and_then(|()| ctrl.start_streaming());
+        match result {
+            Ok(()) => break,
+            Err(ref e) => eprintln!("recovery attempt {attempts} failed: {e}"),
+        }
+    }
+    let _ = tx_recv_cmd.send(RecvCmd::Resume);
+    RecoveryRecord {
+        trigger,
+        started,
+        duration: t0.elapsed(),
+        attempts,
+        result,
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+/// An item leaving a [`ReorderBuffer`], in `pkt_cnt` order.
+pub enum Reordered<T> {
+    Packet(T),
+    /// The packet with this `pkt_cnt` never arrived within the window.
+    Gap(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    Accepted,
+    /// `pkt_cnt` jumped back by more than the window, e.g. after a re-sync
+    /// whose first frames were lost. Everything held back was released first,
+    /// the new sequence starts at `from`.
+    Restart { from: u64 },
+    /// The slot was already given up and emitted as a gap, the packet is dropped.
+    /// A packet far behind the window is also taken as late until the next
+    /// one confirms a restart, see [`ReorderBuffer::push`].
+    Late,
+    /// The same `pkt_cnt` was already received, the packet is dropped.
+    Duplicate,
+}
+
+/// Holds back up to `depth` frames so that packets arriving slightly out of
+/// order are put into their slot. A slot is only declared lost once a packet
+/// at least `depth` frames newer arrives, or on [`flush`](Self::flush).
+pub struct ReorderBuffer<T> {
+    depth: usize,
+    next_cnt: Option<u64>,
+    slots: VecDeque<Option<T>>,
+    emitted_real: VecDeque<bool>,
+    /// The last packet if it was far behind the window, waiting for the next
+    /// one to confirm a restart.
+    restart_candidate: Option<(u64, T)>,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(depth: usize) -> Self {
+        let depth = depth.max(1);
+        Self {
+            depth,
+            next_cnt: None,
+            slots: VecDeque::with_capacity(depth),
+            emitted_real: VecDeque::with_capacity(depth),
+            restart_candidate: None,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Puts `item` into its slot and moves what is complete to `out`.
+    ///
+    /// A packet more than the window behind is only taken as a restart if
+    /// `pkt_cnt` is within the window of 0, or if the packet right after it
+    /// continues its sequence. Otherwise it is a stray late or duplicate
+    /// packet and dropped as [`Arrival::Late`].
+    pub fn push(&mut self, pkt_cnt: u64, item: T, out: &mut VecDeque<Reordered<T>>) -> Arrival {
+        let depth = self.depth as u64;
+        let candidate = self.restart_candidate.take();
+        let mut arrival = Arrival::Accepted;
+        let next = match self.next_cnt {
+            None => pkt_cnt,
+            Some(next) if next.saturating_sub(pkt_cnt) > depth => {
+                let (from, first) = match candidate {
+                    Some((from, first)) if pkt_cnt > from && pkt_cnt - from <= depth => {
+                        (from, Some(first))
+                    }
+                    _ if pkt_cnt < depth => (pkt_cnt, None),
+                    _ => {
+                        self.restart_candidate = Some((pkt_cnt, item));
+                        return Arrival::Late;
+                    }
+                };
+                self.flush(out);
+                self.emitted_real.clear();
+                self.slots.push_back(first);
+                arrival = Arrival::Restart { from };
+                from
+            }
+            Some(next) => next,
+        };
+        self.next_cnt = Some(next);
+
+        if pkt_cnt < next {
+            let age = (next - pkt_cnt) as usize;
+            let n = self.emitted_real.len();
+            return if age <= n && self.emitted_real[n - age] {
+                Arrival::Duplicate
+            } else {
+                Arrival::Late
+            };
+        }
+
+        while pkt_cnt - self.next_cnt.unwrap_or(pkt_cnt) >= self.depth as u64 {
+            self.pop_front(out);
+        }
+
+        let idx = (pkt_cnt - self.next_cnt.unwrap_or(pkt_cnt)) as usize;
+        if self.slots.len() <= idx {
+            self.slots.resize_with(idx + 1, || None);
+        }
+        if self.slots[idx].is_some() {
+            return Arrival::Duplicate;
+        }
+        self.slots[idx] = Some(item);
+
+        while let Some(Some(_)) = self.slots.front() {
+            self.pop_front(out);
+        }
+        arrival
+    }
+
+    /// Releases everything held back, emitting gaps for the slots still missing.
+    pub fn flush(&mut self, out: &mut VecDeque<Reordered<T>>) {
+        while !self.slots.is_empty() {
+            self.pop_front(out);
+        }
+    }
+
+    fn pop_front(&mut self, out: &mut VecDeque<Reordered<T>>) {
+        let Some(ref mut next) = self.next_cnt else {
+            return;
+        };
+        let real = match self.slots.pop_front().flatten() {
+            Some(item) => {
+                out.push_back(Reordered::Packet(item));
+                true
+            }
+            None => {
+                out.push_back(Reordered::Gap(*next));
+                false
+            }
+        };
+        *next += 1;
+
+        if self.emitted_real.len() == self.depth {
+            self.emitted_real.pop_front();
+        }
+        self.emitted_real.push_back(real);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(out: &mut VecDeque<Reordered<u64>>) -> Vec<Result<u64, u64>> {
+        out.drain(..)
+            .map(|r| match r {
+                Reordered::Packet(cnt) => Ok(cnt),
+                Reordered::Gap(cnt) => Err(cnt),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_packet_beyond_window_is_late() {
+        let mut buf = ReorderBuffer::new(16);
+        let mut out = VecDeque::new();
+        for cnt in 100..140 {
+            assert_eq!(buf.push(cnt, cnt, &mut out), Arrival::Accepted);
+        }
+        assert_eq!(emitted(&mut out), (100..140).map(Ok).collect::<Vec<_>>());
+
+        assert_eq!(buf.push(110, 110, &mut out), Arrival::Late);
+        assert!(out.is_empty());
+        for cnt in 140..150 {
+            assert_eq!(buf.push(cnt, cnt, &mut out), Arrival::Accepted);
+        }
+        assert_eq!(emitted(&mut out), (140..150).map(Ok).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn confirmed_restart_keeps_its_first_packet() {
+        let mut buf = ReorderBuffer::new(16);
+        let mut out = VecDeque::new();
+        for cnt in 100..140 {
+            buf.push(cnt, cnt, &mut out);
+        }
+        out.clear();
+
+        assert_eq!(buf.push(50, 50, &mut out), Arrival::Late);
+        assert_eq!(buf.push(51, 51, &mut out), Arrival::Restart { from: 50 });
+        assert_eq!(emitted(&mut out), vec![Ok(50), Ok(51)]);
+
+        assert_eq!(buf.push(3, 3, &mut out), Arrival::Restart { from: 3 });
+        assert_eq!(emitted(&mut out), vec![Ok(3)]);
+    }
+}
@@ -1,44 +1,252 @@
 use std::{
-    net::{SocketAddrV4, UdpSocket},
+    fmt::Display,
+    net::{Ipv4Addr, SocketAddrV4},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
     thread::JoinHandle,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Utc};
 use crossbeam::channel::{Receiver, Sender, bounded};
 use lockfree_object_pool::LinearOwnedReusable;
 use num::Complex;
-use sdaa_ctrl::ctrl_msg::{CmdReplySummary, CtrlMsg, send_cmd};
+use sdaa_ctrl::ctrl_msg::CtrlMsg;
 
-#[cfg(not(feature = "cuda"))]
 use crate::{
-    payload::Payload,
-    pipeline::{DdcCmd, RecvCmd, recv_pkt},
+    array::{ArrayError, BoardStatus},
+    arrival::ArrivalSummary,
+    clock::{ClockDiscipline, ClockFit, NOMINAL_TICK_RATE},
+    error::SdaaError,
+    firmware::FirmwareCheck,
+    health::{DeviceEvent, HealthMonitor},
+    recovery::{Recovery, RecoveryPolicy, RecoveryRecord},
+    stats::{RecvStats, RecvStatsSnapshot},
+    status::DeviceStatus,
+    time_ref::TimeReference,
+    transport::{CmdReplies, CtrlTransport, RetryPolicy, UdpTransport, copy_cmd},
+};
+
+use crate::{
+    payload::{Payload, PayloadValidator},
+    pipeline::{
+        MaybeMulticastReceiver, Membership, PacketSource, RecvCmd, RecvConfig, recv_pkt_from,
+    },
+    utils::set_recv_buffer_size,
 };
 
 #[cfg(feature = "cuda")]
 use crate::{
-    ddc::{N_PT_PER_FRAME, fir_coeffs_full, fir_coeffs_half},
-    payload::Payload,
-    pipeline::{DdcCmd, RecvCmd, pkt_ddc, recv_pkt},
+    ddc::{DownConverter, N_PT_PER_FRAME, fir_coeffs_full, fir_coeffs_half},
+    pipeline::{Block, DdcCmd, pkt_ddc},
 };
 
-pub struct SdrCtrl {
-    pub remote_ctrl_addr: SocketAddrV4,
-    pub local_ctrl_addr: SocketAddrV4,
+#[cfg(feature = "io_uring")]
+use crate::uring::UringReceiver;
+
+/// Where the device is in its bring-up sequence, as far as this host knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceState {
+    Off,
+    Awake,
+    Locked,
+    Initialized,
+    Synced,
+    Streaming,
+    /// A command failed, only [`SdrCtrl::refresh_state`] and
+    /// [`SdrCtrl::bring_up`] leave this state.
+    Faulted,
+}
+
+/// Bring-up step a [`DeviceError`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceStep {
+    Query,
+    Wakeup,
+    Lock,
+    Init,
+    Sync,
+    StreamStart,
+    StreamStop,
+}
+
+#[derive(Debug, Clone)]
+pub enum DeviceError {
+    /// No normal reply, the remote did not answer or rejected the command.
+    NoReply(DeviceStep),
+    /// Replied, but not with what the step expects.
+    AbnormalReply(DeviceStep),
+    LockTimeout {
+        waited: Duration,
+        last: Option<DeviceStatus>,
+    },
+    InvalidTransition {
+        from: DeviceState,
+        step: DeviceStep,
+    },
+    /// `fm_ver` known to stream a payload version this build cannot parse,
+    /// see [`KNOWN_FIRMWARE`](crate::firmware::KNOWN_FIRMWARE).
+    UnsupportedFirmware(u32),
+}
+
+impl Display for DeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceError::NoReply(step) => write!(f, "no reply to {step:?}"),
+            DeviceError::AbnormalReply(step) => write!(f, "abnormal reply to {step:?}"),
+            DeviceError::LockTimeout { waited, last } => {
+                write!(f, "not locked after {waited:?}")?;
+                if let Some(status) = last {
+                    write!(f, ", locked={:#x}", status.locked.raw)?;
+                }
+                Ok(())
+            }
+            DeviceError::InvalidTransition { from, step } => {
+                write!(f, "cannot {step:?} while {from:?}")
+            }
+            DeviceError::UnsupportedFirmware(fm_ver) => {
+                write!(f, "unsupported firmware {fm_ver:#x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
+/// How [`SdrCtrl::lock`] waits for the clocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockPolicy {
+    /// Lock bits are not trusted this soon after wakeup.
+    pub settle: Duration,
+    pub timeout: Duration,
+    pub poll: Duration,
+    /// Carry on without lock after the timeout instead of failing.
+    pub ignore_locking: bool,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            settle: Duration::from_secs(6),
+            timeout: Duration::from_secs(60),
+            poll: Duration::from_secs(1),
+            ignore_locking: false,
+        }
+    }
+}
+
+/// Controls one device, or several as one when built with
+/// [`with_remotes`](Self::with_remotes). Every command goes out to all
+/// remotes in a single exchange, and a step only succeeds once every remote
+/// replied normally.
+pub struct SdrCtrl<T: CtrlTransport = UdpTransport> {
+    remotes: Vec<SocketAddrV4>,
+    transport: T,
+    retry: RetryPolicy,
+    time_ref: Mutex<Option<TimeReference>>,
+    state: Mutex<DeviceState>,
+    woken_at: Mutex<Option<Instant>>,
+    /// Remotes the last query found off, all of them until then.
+    off: Mutex<Vec<SocketAddrV4>>,
+    firmware: Mutex<Option<u32>>,
+    firmware_check: Mutex<FirmwareCheck>,
+    // commands from the application and from background tasks share the local port
+    cmd_lock: Mutex<()>,
 }
 
 impl SdrCtrl {
-    pub fn send_cmd(&self, cmd: CtrlMsg) -> CmdReplySummary {
-        send_cmd(
-            cmd,
-            &[self.remote_ctrl_addr],
-            self.local_ctrl_addr,
-            Some(Duration::from_secs(10)),
-            1,
+    pub fn new(remote_ctrl_addr: SocketAddrV4, local_ctrl_addr: SocketAddrV4) -> Self {
+        Self::with_transport(
+            remote_ctrl_addr,
+            UdpTransport::new(local_ctrl_addr),
+            RetryPolicy::default(),
         )
     }
+}
+
+impl<T: CtrlTransport> SdrCtrl<T> {
+    pub fn with_transport(remote_ctrl_addr: SocketAddrV4, transport: T, retry: RetryPolicy) -> Self {
+        Self::with_remotes(vec![remote_ctrl_addr], transport, retry)
+    }
+
+    /// Several devices driven together, see [`DeviceArray`](crate::array::DeviceArray).
+    pub fn with_remotes(remotes: Vec<SocketAddrV4>, transport: T, retry: RetryPolicy) -> Self {
+        Self {
+            off: Mutex::new(remotes.clone()),
+            remotes,
+            transport,
+            retry,
+            time_ref: Mutex::new(None),
+            state: Mutex::new(DeviceState::Off),
+            woken_at: Mutex::new(None),
+            firmware: Mutex::new(None),
+            firmware_check: Mutex::new(FirmwareCheck::default()),
+            cmd_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn remotes(&self) -> &[SocketAddrV4] {
+        &self.remotes
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    /// `fm_ver` seen by the last [`refresh_state`](Self::refresh_state),
+    /// `None` if the remotes disagree.
+    pub fn firmware_version(&self) -> Option<u32> {
+        *self.firmware.lock().unwrap()
+    }
+
+    /// Applied by [`refresh_state`](Self::refresh_state), ignored by default.
+    pub fn set_firmware_check(&self, check: FirmwareCheck) {
+        *self.firmware_check.lock().unwrap() = check;
+    }
+
+    /// Time reference of the last successful [`sync`](Self::sync).
+    pub fn time_ref(&self) -> Option<TimeReference> {
+        *self.time_ref.lock().unwrap()
+    }
+
+    /// Sends `cmd`, repeating it as the retry policy says to the remotes
+    /// that did not reply.
+    pub fn send_cmd(&self, cmd: CtrlMsg) -> CmdReplies {
+        self.send_cmd_to(cmd, &self.remotes, self.retry.retries)
+    }
+
+    fn send_cmd_to(&self, cmd: CtrlMsg, remotes: &[SocketAddrV4], retries: usize) -> CmdReplies {
+        let _guard = self.cmd_lock.lock().unwrap();
+        let mut replies = CmdReplies::default();
+        let mut missing = remotes.to_vec();
+        let mut next = Some(cmd);
+        for attempt in 0..=retries {
+            let Some(cmd) = next.take() else {
+                break;
+            };
+            if attempt > 0 {
+                std::thread::sleep(self.retry.backoff(attempt));
+            }
+            next = copy_cmd(&cmd);
+            let again = self.transport.exchange(cmd, &missing, self.retry.timeout);
+            replies.normal.extend(again.normal);
+            missing.retain(|&r| !replies.replied(r));
+            if missing.is_empty() {
+                break;
+            }
+        }
+        replies
+    }
 
-    pub fn wakeup(&self) -> CmdReplySummary {
+    pub fn wakeup(&self) -> CmdReplies {
         let cmd = CtrlMsg::PwrCtrl {
             msg_id: 0,
             op_code: 1,
@@ -47,48 +255,15 @@ impl SdrCtrl {
     }
 
     pub fn awaken_and_locked(&self) -> Option<bool> {
-        let reply = self.query();
-        if reply.normal_reply.len() != 1 {
-            None
-        } else if let (
-            _,
-            CtrlMsg::QueryReply {
-                msg_id: _,
-                fm_ver: _,
-                tick_cnt1: _,
-                tick_cnt2: _,
-                trans_state,
-                locked,
-                health: _,
-            },
-        ) = reply.normal_reply[0]
-        {
-            println!("stat={trans_state:x} {locked:x}");
-            Some(trans_state & 0b10 != 0 && (locked == 0x3f || locked == 0x2f))
-        } else {
-            None
-        }
+        let status = self.status()?;
+        println!("stat={:x} {:x}", status.trans_state, status.locked.raw);
+        Some(status.is_ready())
     }
 
     pub fn wait_until_locked(&self, timeout_sec: usize) -> bool {
         std::thread::sleep(Duration::from_secs(6));
         for _i in 0..timeout_sec {
-            let reply = self.query();
-            if !reply.normal_reply.is_empty()
-                && let (
-                    _a,
-                    CtrlMsg::QueryReply {
-                        msg_id: _,
-                        fm_ver: _,
-                        tick_cnt1: _,
-                        tick_cnt2: _,
-                        trans_state: _,
-                        ref locked,
-                        health: _,
-                    },
-                ) = reply.normal_reply[0]
-                && (*locked == 0x3f || *locked == 0x2f)
-            {
+            if self.status().is_some_and(|s| s.is_locked()) {
                 return true;
             }
 
@@ -97,17 +272,57 @@ impl SdrCtrl {
         false
     }
 
-    pub fn query(&self) -> CmdReplySummary {
+    /// Queries the device and decodes the reply, `None` unless exactly one
+    /// remote replied.
+    pub fn status(&self) -> Option<DeviceStatus> {
+        DeviceStatus::from_replies(&self.query())
+    }
+
+    /// Queries every remote.
+    pub fn board_status(&self) -> Vec<BoardStatus> {
+        self.board_status_from(&self.query())
+    }
+
+    fn board_status_from(&self, replies: &CmdReplies) -> Vec<BoardStatus> {
+        self.remotes
+            .iter()
+            .map(|&addr| BoardStatus {
+                addr,
+                status: replies.status(addr),
+            })
+            .collect()
+    }
+
+    pub fn query(&self) -> CmdReplies {
         let cmd = CtrlMsg::Query { msg_id: 0 };
         self.send_cmd(cmd)
     }
 
-    pub fn sync(&self) -> CmdReplySummary {
+    /// Device tick counter and the host time it was read at, taken halfway
+    /// through the query round trip.
+    pub fn query_ticks(&self) -> Option<(DateTime<Utc>, u64)> {
+        let sent = Utc::now();
+        let status = self.status()?;
+        let replied = Utc::now();
+        Some((sent + (replied - sent) / 2, status.ticks))
+    }
+
+    /// Sample 0 of the stream is taken at the sync, so the epoch is put
+    /// halfway between sending the command and getting the reply. Never
+    /// retried, a remote syncing on a retry would be off by the timeout,
+    /// and the epoch is only recorded if every remote replied.
+    pub fn sync(&self) -> CmdReplies {
         let cmd = CtrlMsg::Sync { msg_id: 0 };
-        self.send_cmd(cmd)
+        let sent = Utc::now();
+        let replies = self.send_cmd_to(cmd, &self.remotes, 0);
+        let replied_at = Utc::now();
+        if self.remotes.iter().all(|&r| replies.replied(r)) {
+            *self.time_ref.lock().unwrap() = Some(TimeReference::from_sync(sent, replied_at));
+        }
+        replies
     }
 
-    pub fn init(&self) -> CmdReplySummary {
+    pub fn init(&self) -> CmdReplies {
         let cmd = CtrlMsg::Init {
             msg_id: 0,
             reserved_zeros: 0,
@@ -115,18 +330,267 @@ impl SdrCtrl {
         self.send_cmd(cmd)
     }
 
-    pub fn stream_start(&self) -> CmdReplySummary {
+    pub fn stream_start(&self) -> CmdReplies {
         let cmd = CtrlMsg::StreamStart { msg_id: 0 };
         self.send_cmd(cmd)
     }
 
-    pub fn stream_stop(&self) -> CmdReplySummary {
+    pub fn stream_stop(&self) -> CmdReplies {
         println!("stopped");
         let cmd = CtrlMsg::StreamStop { msg_id: 0 };
         self.send_cmd(cmd)
     }
 }
 
+/// Checked bring-up. Each step validates the replies and the state it is
+/// called in, the raw commands above do neither. The `*_all` variants
+/// report every remote that failed, the others only the first one.
+impl<T: CtrlTransport> SdrCtrl<T> {
+    pub fn state(&self) -> DeviceState {
+        *self.state.lock().unwrap()
+    }
+
+    fn set_state(&self, state: DeviceState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// Fails with `InvalidTransition` unless the state is within `allowed`.
+    fn expect_state(
+        &self,
+        step: DeviceStep,
+        allowed: impl std::ops::RangeBounds<DeviceState>,
+    ) -> Result<DeviceState, ArrayError> {
+        let from = self.state();
+        if from != DeviceState::Faulted && allowed.contains(&from) {
+            return Ok(from);
+        }
+        let e = DeviceError::InvalidTransition { from, step };
+        Err(ArrayError {
+            step,
+            failed: self.remotes.iter().map(|&r| (r, e.clone())).collect(),
+        })
+    }
+
+    /// Fails with the remotes in `remotes` that did not reply normally,
+    /// going to `Faulted` if there are any.
+    fn check_replies(
+        &self,
+        step: DeviceStep,
+        remotes: &[SocketAddrV4],
+        replies: &CmdReplies,
+    ) -> Result<(), ArrayError> {
+        let failed: Vec<(SocketAddrV4, DeviceError)> = remotes
+            .iter()
+            .filter(|&&r| !replies.replied(r))
+            .map(|&r| (r, DeviceError::NoReply(step)))
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        self.set_state(DeviceState::Faulted);
+        Err(ArrayError { step, failed })
+    }
+
+    fn checked(&self, step: DeviceStep, cmd: CtrlMsg, retries: usize) -> Result<(), ArrayError> {
+        let replies = self.send_cmd_to(cmd, &self.remotes, retries);
+        self.check_replies(step, &self.remotes, &replies)
+    }
+
+    /// Stops any stream and derives the state from a query. Initialization
+    /// and sync cannot be queried, so at most `Locked` comes out of this.
+    /// The firmware version is checked here too, see [`FirmwareCheck`].
+    pub fn refresh_state(&self) -> Result<DeviceState, DeviceError> {
+        self.refresh_state_all().map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn refresh_state_all(&self) -> Result<DeviceState, ArrayError> {
+        // a remote that does not answer this fails the query right after
+        self.stream_stop();
+        let replies = self.query();
+        self.check_replies(DeviceStep::Query, &self.remotes, &replies)?;
+        let check = *self.firmware_check.lock().unwrap();
+        let mut failed = Vec::new();
+        let mut off = Vec::new();
+        let mut fm_vers = Vec::new();
+        let mut state = DeviceState::Locked;
+        for b in self.board_status_from(&replies) {
+            let Some(s) = b.status else {
+                failed.push((b.addr, DeviceError::AbnormalReply(DeviceStep::Query)));
+                continue;
+            };
+            fm_vers.push(s.fm_ver);
+            if let Err(e) = check.verify(s.fm_ver) {
+                failed.push((b.addr, e));
+            } else if s.is_ready() {
+                continue;
+            } else if s.is_awake() {
+                state = state.min(DeviceState::Awake);
+            } else {
+                off.push(b.addr);
+                state = DeviceState::Off;
+            }
+        }
+        fm_vers.sort_unstable();
+        fm_vers.dedup();
+        if fm_vers.len() > 1 {
+            eprintln!("warning: boards run different firmware {fm_vers:x?}");
+        }
+        *self.firmware.lock().unwrap() = match fm_vers[..] {
+            [fm_ver] => Some(fm_ver),
+            _ => None,
+        };
+        if !failed.is_empty() {
+            self.set_state(DeviceState::Faulted);
+            return Err(ArrayError {
+                step: DeviceStep::Query,
+                failed,
+            });
+        }
+        *self.off.lock().unwrap() = off;
+        self.set_state(state);
+        Ok(state)
+    }
+
+    pub fn power_up(&self) -> Result<(), DeviceError> {
+        self.power_up_all().map_err(ArrayError::into_first)
+    }
+
+    /// Only wakes the remotes that are off.
+    pub(crate) fn power_up_all(&self) -> Result<(), ArrayError> {
+        if self.expect_state(DeviceStep::Wakeup, ..)? != DeviceState::Off {
+            return Ok(());
+        }
+        let off = self.off.lock().unwrap().clone();
+        let cmd = CtrlMsg::PwrCtrl {
+            msg_id: 0,
+            op_code: 1,
+        };
+        let replies = self.send_cmd_to(cmd, &off, self.retry.retries);
+        self.check_replies(DeviceStep::Wakeup, &off, &replies)?;
+        *self.woken_at.lock().unwrap() = Some(Instant::now());
+        self.set_state(DeviceState::Awake);
+        Ok(())
+    }
+
+    /// Waits for the clocks to lock, see [`LockPolicy`].
+    pub fn lock(&self, policy: &LockPolicy) -> Result<(), DeviceError> {
+        self.lock_all(policy).map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn lock_all(&self, policy: &LockPolicy) -> Result<(), ArrayError> {
+        if self.expect_state(DeviceStep::Lock, DeviceState::Awake..)? != DeviceState::Awake {
+            return Ok(());
+        }
+        if let Some(t) = *self.woken_at.lock().unwrap() {
+            std::thread::sleep((t + policy.settle).saturating_duration_since(Instant::now()));
+        }
+        let t0 = Instant::now();
+        let mut last = vec![None; self.remotes.len()];
+        loop {
+            for (last, b) in last.iter_mut().zip(self.board_status()) {
+                *last = b.status.or(*last);
+            }
+            let failed: Vec<(SocketAddrV4, DeviceError)> = self
+                .remotes
+                .iter()
+                .zip(&last)
+                .filter(|(_, s)| !s.is_some_and(|s| s.is_locked()))
+                .map(|(&r, &last)| {
+                    let waited = t0.elapsed();
+                    (r, DeviceError::LockTimeout { waited, last })
+                })
+                .collect();
+            if failed.is_empty() {
+                break;
+            }
+            if t0.elapsed() >= policy.timeout {
+                if policy.ignore_locking {
+                    eprintln!("ignoring clock locking on {} remotes", failed.len());
+                    break;
+                }
+                return Err(ArrayError {
+                    step: DeviceStep::Lock,
+                    failed,
+                });
+            }
+            std::thread::sleep(policy.poll);
+        }
+        self.set_state(DeviceState::Locked);
+        Ok(())
+    }
+
+    /// Allowed again after a sync, the stream has to be stopped though.
+    pub fn initialize(&self) -> Result<(), DeviceError> {
+        self.initialize_all().map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn initialize_all(&self) -> Result<(), ArrayError> {
+        self.expect_state(DeviceStep::Init, DeviceState::Locked..DeviceState::Streaming)?;
+        let cmd = CtrlMsg::Init {
+            msg_id: 0,
+            reserved_zeros: 0,
+        };
+        self.checked(DeviceStep::Init, cmd, self.retry.retries)?;
+        self.set_state(DeviceState::Initialized);
+        Ok(())
+    }
+
+    /// Fails unless every remote replied to the single `Sync`, see [`sync`](Self::sync).
+    pub fn synchronize(&self) -> Result<(), DeviceError> {
+        self.synchronize_all().map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn synchronize_all(&self) -> Result<(), ArrayError> {
+        self.expect_state(DeviceStep::Sync, DeviceState::Initialized..DeviceState::Streaming)?;
+        let replies = self.sync();
+        self.check_replies(DeviceStep::Sync, &self.remotes, &replies)?;
+        self.set_state(DeviceState::Synced);
+        Ok(())
+    }
+
+    /// Not retried, a start that got through but whose reply was lost
+    /// would otherwise be sent twice.
+    pub fn start_streaming(&self) -> Result<(), DeviceError> {
+        self.start_streaming_all().map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn start_streaming_all(&self) -> Result<(), ArrayError> {
+        if self.expect_state(DeviceStep::StreamStart, DeviceState::Synced..)? == DeviceState::Streaming {
+            return Ok(());
+        }
+        self.checked(DeviceStep::StreamStart, CtrlMsg::StreamStart { msg_id: 0 }, 0)?;
+        self.set_state(DeviceState::Streaming);
+        Ok(())
+    }
+
+    pub fn stop_streaming(&self) -> Result<(), DeviceError> {
+        self.stop_streaming_all().map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn stop_streaming_all(&self) -> Result<(), ArrayError> {
+        let cmd = CtrlMsg::StreamStop { msg_id: 0 };
+        self.checked(DeviceStep::StreamStop, cmd, self.retry.retries)?;
+        if self.state() == DeviceState::Streaming {
+            self.set_state(DeviceState::Synced);
+        }
+        Ok(())
+    }
+
+    /// Runs everything up to `Synced` from whatever state the device is in,
+    /// skipping the steps that are already done.
+    pub fn bring_up(&self, policy: &LockPolicy) -> Result<(), DeviceError> {
+        self.bring_up_all(policy).map_err(ArrayError::into_first)
+    }
+
+    pub(crate) fn bring_up_all(&self, policy: &LockPolicy) -> Result<(), ArrayError> {
+        self.refresh_state_all()?;
+        self.power_up_all()?;
+        self.lock_all(policy)?;
+        self.initialize_all()?;
+        self.synchronize_all()
+    }
+}
+
 #[cfg(feature = "cuda")]
 #[derive(Debug, Clone, Copy)]
 pub enum SdrSmpRate {
@@ -143,12 +607,539 @@ impl SdrSmpRate {
         }
     }
 
-    pub fn from_ndec(ndec: usize) -> SdrSmpRate {
+    pub fn from_ndec(ndec: usize) -> Result<SdrSmpRate, SdaaError> {
         match ndec {
-            2 => SdrSmpRate::SmpRate240,
-            4 => SdrSmpRate::SmpRate120,
-            _ => panic!("invalid ndec"),
+            2 => Ok(SdrSmpRate::SmpRate240),
+            4 => Ok(SdrSmpRate::SmpRate120),
+            _ => Err(SdaaError::InvalidConfig(format!(
+                "ndec {ndec} not supported, expected 2 or 4"
+            ))),
+        }
+    }
+}
+
+/// Depth of the payload and output queues unless set otherwise.
+pub const QUEUE_LEN: usize = 8192;
+
+/// How the payload socket is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecvBackend {
+    /// Blocking `recvmsg`, one datagram per call.
+    #[default]
+    Socket,
+    /// Multishot receive into `nbufs` kernel-provided buffers, see
+    /// [`UringReceiver`](crate::uring::UringReceiver).
+    #[cfg(feature = "io_uring")]
+    Uring { nbufs: u16 },
+}
+
+/// Sets up an [`Sdr`] or a [`RawSdr`] with more than the defaults of
+/// [`Sdr::new`] and [`RawSdr::new`].
+#[derive(Debug, Clone)]
+pub struct SdrBuilder {
+    /// Remote and local control address, `None` when only listening.
+    ctrl_addrs: Option<(SocketAddrV4, SocketAddrV4)>,
+    local_payload_addr: SocketAddrV4,
+    #[cfg(feature = "cuda")]
+    ndec: usize,
+    #[cfg(feature = "cuda")]
+    fir_coeffs: Option<Vec<f32>>,
+    #[cfg(feature = "cuda")]
+    lo_ch: isize,
+    #[cfg(feature = "cuda")]
+    output_capacity: usize,
+    payload_capacity: usize,
+    memberships: Vec<Membership>,
+    recv_buffer_size: Option<usize>,
+    backend: RecvBackend,
+    recv_config: RecvConfig,
+    firmware_check: FirmwareCheck,
+    lock_policy: Option<LockPolicy>,
+}
+
+impl SdrBuilder {
+    pub fn new(
+        remote_ctrl_addr: SocketAddrV4,
+        local_ctrl_addr: SocketAddrV4,
+        local_payload_addr: SocketAddrV4,
+    ) -> Self {
+        Self {
+            ctrl_addrs: Some((remote_ctrl_addr, local_ctrl_addr)),
+            ..Self::listen_only(local_payload_addr)
+        }
+    }
+
+    /// Receives without a control connection, e.g. from a multicast group
+    /// another host brings up, or from a replay. Nothing is ever sent to the
+    /// device, so the firmware check and [`bring_up`](Self::bring_up) do not
+    /// apply, the stream is neither stopped nor started, and the health
+    /// monitor, clock discipline and recovery are not available.
+    pub fn listen_only(local_payload_addr: SocketAddrV4) -> Self {
+        Self {
+            ctrl_addrs: None,
+            local_payload_addr,
+            #[cfg(feature = "cuda")]
+            ndec: SdrSmpRate::SmpRate240.to_ndec(),
+            #[cfg(feature = "cuda")]
+            fir_coeffs: None,
+            #[cfg(feature = "cuda")]
+            lo_ch: N_PT_PER_FRAME as isize / 4,
+            #[cfg(feature = "cuda")]
+            output_capacity: QUEUE_LEN,
+            payload_capacity: QUEUE_LEN,
+            memberships: Vec::new(),
+            recv_buffer_size: None,
+            backend: RecvBackend::default(),
+            recv_config: RecvConfig::default(),
+            firmware_check: FirmwareCheck::default(),
+            lock_policy: None,
+        }
+    }
+
+    #[cfg(feature = "cuda")]
+    pub fn smp_rate(self, smp_rate: SdrSmpRate) -> Self {
+        self.ndec(smp_rate.to_ndec())
+    }
+
+    /// Decimation of the DDC. Only 2 and 4 come with a default filter, any
+    /// other needs [`fir_coeffs`](Self::fir_coeffs).
+    #[cfg(feature = "cuda")]
+    pub fn ndec(mut self, ndec: usize) -> Self {
+        self.ndec = ndec;
+        self
+    }
+
+    /// Filter taps of the DDC, their number a multiple of the decimation.
+    #[cfg(feature = "cuda")]
+    pub fn fir_coeffs(mut self, fir_coeffs: Vec<f32>) -> Self {
+        self.fir_coeffs = Some(fir_coeffs);
+        self
+    }
+
+    /// LO channel the DDC starts with, a quarter of the band by default.
+    #[cfg(feature = "cuda")]
+    pub fn lo_ch(mut self, lo_ch: isize) -> Self {
+        self.lo_ch = lo_ch;
+        self
+    }
+
+    /// Number of DDC blocks queued for the application.
+    #[cfg(feature = "cuda")]
+    pub fn output_capacity(mut self, capacity: usize) -> Self {
+        self.output_capacity = capacity;
+        self
+    }
+
+    /// Number of received frames queued for the next stage.
+    pub fn payload_capacity(mut self, capacity: usize) -> Self {
+        self.payload_capacity = capacity;
+        self
+    }
+
+    /// Joins `group` on the interface with address `iface`, from any sender.
+    pub fn multicast(self, group: Ipv4Addr, iface: Ipv4Addr) -> Self {
+        self.membership(Membership::any_source(group, iface))
+    }
+
+    /// Joins another multicast group, possibly source-specific. Several
+    /// groups need the payload address to be unspecified, and all of them
+    /// must carry this device's stream.
+    pub fn membership(mut self, membership: Membership) -> Self {
+        self.memberships.push(membership);
+        self
+    }
+
+    /// `SO_RCVBUF` of the payload socket, capped by `net.core.rmem_max`.
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    pub fn backend(mut self, backend: RecvBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn recv_config(mut self, config: RecvConfig) -> Self {
+        self.recv_config = config;
+        self
+    }
+
+    /// Unless `Ignore`, also drops packets whose version does not belong to
+    /// the firmware, see [`PayloadValidator::for_firmware`].
+    pub fn firmware_check(mut self, check: FirmwareCheck) -> Self {
+        self.firmware_check = check;
+        self
+    }
+
+    /// Brings the device up with `policy` before the pipeline starts,
+    /// otherwise that is left to the application.
+    pub fn bring_up(mut self, policy: LockPolicy) -> Self {
+        self.lock_policy = Some(policy);
+        self
+    }
+
+    /// Binds the payload socket as configured.
+    fn open_socket(&self) -> Result<MaybeMulticastReceiver, SdaaError> {
+        let socket =
+            MaybeMulticastReceiver::with_memberships(self.local_payload_addr, &self.memberships)?;
+        if let Some(size) = self.recv_buffer_size {
+            set_recv_buffer_size(&socket, size)?;
+        }
+        socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+        Ok(socket)
+    }
+
+    /// Gets the device ready, nothing is received yet. Either way the
+    /// stream is stopped and the firmware checked. `None` when only
+    /// listening.
+    fn connect(&self) -> Result<Option<Arc<SdrCtrl>>, SdaaError> {
+        let Some((remote_ctrl_addr, local_ctrl_addr)) = self.ctrl_addrs else {
+            return Ok(None);
+        };
+        let ctrl = Arc::new(SdrCtrl::new(remote_ctrl_addr, local_ctrl_addr));
+        ctrl.set_firmware_check(self.firmware_check);
+        match &self.lock_policy {
+            Some(policy) => ctrl.bring_up(policy)?,
+            None => {
+                ctrl.refresh_state()?;
+            }
+        }
+        Ok(Some(ctrl))
+    }
+
+    /// The receive settings, only accepting the payload version of the
+    /// device firmware unless the firmware check is off.
+    fn recv_config_for(&self, ctrl: Option<&Arc<SdrCtrl>>) -> RecvConfig {
+        let mut config = self.recv_config.clone();
+        if self.firmware_check != FirmwareCheck::Ignore
+            && let Some(fm_ver) = ctrl.and_then(|c| c.firmware_version())
+        {
+            config.validator = PayloadValidator::for_firmware(fm_ver);
         }
+        config
+    }
+
+    fn spawn_source<S: PacketSource + Send + 'static>(
+        &self,
+        source: S,
+        config: RecvConfig,
+        recv_stats: &Arc<RecvStats>,
+        tx_payload: Sender<LinearOwnedReusable<Payload>>,
+        rx_recv_cmd: Receiver<RecvCmd>,
+    ) -> JoinHandle<()> {
+        let stats = Arc::clone(recv_stats);
+        std::thread::spawn(move || recv_pkt_from(source, config, stats, tx_payload, rx_recv_cmd))
+    }
+
+    /// Reads `socket` with the configured backend.
+    fn spawn_receiver(
+        &self,
+        socket: MaybeMulticastReceiver,
+        config: RecvConfig,
+        recv_stats: &Arc<RecvStats>,
+        tx_payload: Sender<LinearOwnedReusable<Payload>>,
+        rx_recv_cmd: Receiver<RecvCmd>,
+    ) -> Result<JoinHandle<()>, SdaaError> {
+        let thread = match self.backend {
+            RecvBackend::Socket => {
+                self.spawn_source(socket, config, recv_stats, tx_payload, rx_recv_cmd)
+            }
+            #[cfg(feature = "io_uring")]
+            RecvBackend::Uring { nbufs } => {
+                let source = UringReceiver::new(socket, nbufs)?;
+                self.spawn_source(source, config, recv_stats, tx_payload, rx_recv_cmd)
+            }
+        };
+        Ok(thread)
+    }
+
+    #[cfg(feature = "cuda")]
+    #[allow(clippy::type_complexity)]
+    pub fn build(
+        self,
+    ) -> Result<
+        (
+            Sdr,
+            Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+            Sender<DdcCmd>,
+        ),
+        SdaaError,
+    > {
+        let socket = self.open_socket()?;
+        self.build_ddc(|config, stats, tx, rx| {
+            self.spawn_receiver(socket, config, stats, tx, rx)
+        })
+    }
+
+    /// Same as [`build`](Self::build), but takes the packets from `source`
+    /// instead of a socket. The payload address, multicast groups, receive
+    /// buffer size and backend are not used.
+    #[cfg(feature = "cuda")]
+    #[allow(clippy::type_complexity)]
+    pub fn build_with_source<S: PacketSource + Send + 'static>(
+        self,
+        source: S,
+    ) -> Result<
+        (
+            Sdr,
+            Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+            Sender<DdcCmd>,
+        ),
+        SdaaError,
+    > {
+        self.build_ddc(|config, stats, tx, rx| {
+            Ok(self.spawn_source(source, config, stats, tx, rx))
+        })
+    }
+
+    #[cfg(feature = "cuda")]
+    #[allow(clippy::type_complexity)]
+    fn build_ddc(
+        &self,
+        spawn_receiver: impl FnOnce(
+            RecvConfig,
+            &Arc<RecvStats>,
+            Sender<LinearOwnedReusable<Payload>>,
+            Receiver<RecvCmd>,
+        ) -> Result<JoinHandle<()>, SdaaError>,
+    ) -> Result<
+        (
+            Sdr,
+            Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+            Sender<DdcCmd>,
+        ),
+        SdaaError,
+    > {
+        let fir_coeffs = match (&self.fir_coeffs, self.ndec) {
+            (Some(c), _) => c.clone(),
+            (None, 2) => fir_coeffs_full(),
+            (None, 4) => fir_coeffs_half(),
+            (None, n) => {
+                return Err(SdaaError::InvalidConfig(format!(
+                    "no default filter for ndec {n}"
+                )));
+            }
+        };
+        // before the device is touched, so a bad filter fails early
+        let ddc = DownConverter::new(self.ndec, &fir_coeffs)?;
+
+        let ctrl = self.connect()?;
+        let (tx_payload, rx_payload) =
+            bounded::<LinearOwnedReusable<Payload>>(self.payload_capacity);
+        let (tx_ddc, rx_ddc) =
+            bounded::<LinearOwnedReusable<Block<Complex<f32>>>>(self.output_capacity);
+        let (tx_ddc_cmd, rx_ddc_cmd) = bounded::<DdcCmd>(32);
+        let (tx_recv_cmd, rx_recv_cmd) = bounded::<RecvCmd>(32);
+        let tx_recv_cmd1 = tx_recv_cmd.clone();
+
+        tx_ddc_cmd.send(DdcCmd::LoCh(self.lo_ch))?;
+        let recv_stats = Arc::new(RecvStats::default());
+        let rx_thread =
+            spawn_receiver(self.recv_config_for(ctrl.as_ref()), &recv_stats, tx_payload, rx_recv_cmd)?;
+        let ddc_thread = std::thread::spawn(move || {
+            if let Err(e) = pkt_ddc(rx_payload, tx_ddc, ddc, rx_ddc_cmd, tx_recv_cmd) {
+                eprintln!("ddc stopped: {e}");
+            }
+        });
+
+        Ok((
+            Sdr {
+                rx_thread: Some(rx_thread),
+                ddc_thread: Some(ddc_thread),
+                device: SdrDevice::new(ctrl, recv_stats, tx_recv_cmd1),
+            },
+            rx_ddc,
+            tx_ddc_cmd,
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn build_raw(
+        self,
+    ) -> Result<
+        (
+            RawSdr,
+            Receiver<LinearOwnedReusable<Payload>>,
+            Sender<RecvCmd>,
+        ),
+        SdaaError,
+    > {
+        let socket = self.open_socket()?;
+        self.build_raw_from(|config, stats, tx, rx| {
+            self.spawn_receiver(socket, config, stats, tx, rx)
+        })
+    }
+
+    /// Same as [`build_raw`](Self::build_raw), but takes the packets from
+    /// `source` instead of a socket, see [`build_with_source`](Self::build_with_source).
+    #[allow(clippy::type_complexity)]
+    pub fn build_raw_with_source<S: PacketSource + Send + 'static>(
+        self,
+        source: S,
+    ) -> Result<
+        (
+            RawSdr,
+            Receiver<LinearOwnedReusable<Payload>>,
+            Sender<RecvCmd>,
+        ),
+        SdaaError,
+    > {
+        self.build_raw_from(|config, stats, tx, rx| {
+            Ok(self.spawn_source(source, config, stats, tx, rx))
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn build_raw_from(
+        &self,
+        spawn_receiver: impl FnOnce(
+            RecvConfig,
+            &Arc<RecvStats>,
+            Sender<LinearOwnedReusable<Payload>>,
+            Receiver<RecvCmd>,
+        ) -> Result<JoinHandle<()>, SdaaError>,
+    ) -> Result<
+        (
+            RawSdr,
+            Receiver<LinearOwnedReusable<Payload>>,
+            Sender<RecvCmd>,
+        ),
+        SdaaError,
+    > {
+        let ctrl = self.connect()?;
+        let (tx_payload, rx_payload) =
+            bounded::<LinearOwnedReusable<Payload>>(self.payload_capacity);
+        let (tx_recv_cmd, rx_recv_cmd) = bounded::<RecvCmd>(32);
+        let tx_recv_cmd1 = tx_recv_cmd.clone();
+        let recv_stats = Arc::new(RecvStats::default());
+        let rx_thread =
+            spawn_receiver(self.recv_config_for(ctrl.as_ref()), &recv_stats, tx_payload, rx_recv_cmd)?;
+        Ok((
+            RawSdr {
+                rx_thread: Some(rx_thread),
+                device: SdrDevice::new(ctrl, recv_stats, tx_recv_cmd1),
+            },
+            rx_payload,
+            tx_recv_cmd,
+        ))
+    }
+}
+
+/// The device side of [`Sdr`] and [`RawSdr`]: control, receive counters
+/// and the background tasks watching the device. Both deref to it.
+pub struct SdrDevice {
+    recv_stats: Arc<RecvStats>,
+    clock: Option<ClockDiscipline>,
+    health: Option<HealthMonitor>,
+    recovery: Option<Recovery>,
+    tx_recv_cmd: Sender<RecvCmd>,
+    /// `None` if built [`listen_only`](SdrBuilder::listen_only).
+    pub ctrl: Option<Arc<SdrCtrl>>,
+}
+
+impl SdrDevice {
+    fn new(
+        ctrl: Option<Arc<SdrCtrl>>,
+        recv_stats: Arc<RecvStats>,
+        tx_recv_cmd: Sender<RecvCmd>,
+    ) -> Self {
+        Self {
+            recv_stats,
+            clock: None,
+            health: None,
+            recovery: None,
+            tx_recv_cmd,
+            ctrl,
+        }
+    }
+
+    /// The control connection, [`SdaaError::NoControl`] when only listening.
+    pub fn control(&self) -> Result<&Arc<SdrCtrl>, SdaaError> {
+        self.ctrl.as_ref().ok_or(SdaaError::NoControl)
+    }
+
+    pub fn recv_stats(&self) -> RecvStatsSnapshot {
+        self.recv_stats.snapshot()
+    }
+
+    /// `Payload.version` of the stream, once a packet has been accepted.
+    pub fn payload_version(&self) -> Option<u32> {
+        self.recv_stats.payload_version()
+    }
+
+    /// Packet timing, only available if receive timestamps are enabled.
+    pub fn arrival_summary(&self) -> Option<ArrivalSummary> {
+        self.recv_stats.arrival()
+    }
+
+    /// Starts querying the device tick counter every `period` to track the
+    /// clock offset and drift, see [`ClockDiscipline`].
+    pub fn start_clock_discipline(&mut self, period: Duration) -> Result<(), SdaaError> {
+        self.clock = Some(ClockDiscipline::spawn(
+            Arc::clone(self.control()?),
+            period,
+            NOMINAL_TICK_RATE,
+        ));
+        Ok(())
+    }
+
+    pub fn clock_fit(&self) -> Option<ClockFit> {
+        self.clock.as_ref().and_then(|c| c.fit())
+    }
+
+    /// Time reference of the last sync, corrected by the clock fit if one is
+    /// available and agrees with the sync, see [`ClockFit::correct`].
+    pub fn time_ref(&self) -> Option<TimeReference> {
+        let time_ref = self.ctrl.as_ref()?.time_ref()?;
+        Some(self.clock_fit().map_or(time_ref, |fit| fit.correct(&time_ref)))
+    }
+
+    /// Starts querying the device every `period` for lock and health
+    /// changes, see [`HealthMonitor`]. Replaces a running monitor.
+    pub fn start_health_monitor(
+        &mut self,
+        period: Duration,
+    ) -> Result<Receiver<DeviceEvent>, SdaaError> {
+        let monitor = HealthMonitor::spawn(Arc::clone(self.control()?), period);
+        let rx = monitor.subscribe();
+        self.health = Some(monitor);
+        Ok(rx)
+    }
+
+    /// Another channel of device events, `None` if no monitor is running.
+    pub fn device_events(&self) -> Option<Receiver<DeviceEvent>> {
+        self.health.as_ref().map(|h| h.subscribe())
+    }
+
+    /// Device status from the latest health query.
+    pub fn last_status(&self) -> Option<DeviceStatus> {
+        self.health.as_ref().and_then(|h| h.last_status())
+    }
+
+    /// Brings the device back up on lock loss or a stream restart, see
+    /// [`Recovery`]. Starts a health monitor polling every `policy.poll`
+    /// unless one is running.
+    pub fn start_recovery(&mut self, policy: RecoveryPolicy) -> Result<(), SdaaError> {
+        let ctrl = Arc::clone(self.control()?);
+        let events = match self.device_events() {
+            Some(rx) => rx,
+            None => self.start_health_monitor(policy.poll)?,
+        };
+        self.recovery = Some(Recovery::spawn(
+            ctrl,
+            Arc::clone(&self.recv_stats),
+            self.tx_recv_cmd.clone(),
+            events,
+            policy,
+        ));
+        Ok(())
+    }
+
+    pub fn recovery_history(&self) -> Vec<RecoveryRecord> {
+        self.recovery
+            .as_ref()
+            .map_or_else(Vec::new, |r| r.history())
     }
 }
 
@@ -156,14 +1147,16 @@ impl SdrSmpRate {
 pub struct Sdr {
     rx_thread: Option<JoinHandle<()>>,
     ddc_thread: Option<JoinHandle<()>>,
-    pub ctrl: SdrCtrl,
+    device: SdrDevice,
 }
 
 #[cfg(feature = "cuda")]
 impl Drop for Sdr {
     fn drop(&mut self) {
         eprintln!("dropped");
-        self.ctrl.stream_stop();
+        if let Some(ctrl) = &self.device.ctrl {
+            ctrl.stream_stop();
+        }
         let h = self.ddc_thread.take();
         eprintln!("drop1");
         if let Some(h1) = h
@@ -180,75 +1173,77 @@ impl Drop for Sdr {
 
 #[cfg(feature = "cuda")]
 impl Sdr {
+    /// Stops the stream and queries the device, failing unless it answers
+    /// and runs acceptable firmware. The rest of the bring-up is left to the
+    /// caller, or use [`SdrBuilder::bring_up`].
     #[allow(clippy::type_complexity)]
     pub fn new(
         remote_ctrl_addr: SocketAddrV4,
         local_ctrl_addr: SocketAddrV4,
         local_payload_addr: SocketAddrV4,
         smp_rate: SdrSmpRate,
-    ) -> (
-        Sdr,
-        Receiver<LinearOwnedReusable<Vec<Complex<f32>>>>,
-        Sender<DdcCmd>,
-    ) {
-        let payload_socket =
-            UdpSocket::bind(local_payload_addr).expect("failed to bind payload socket");
-
-        send_cmd(
-            CtrlMsg::StreamStop { msg_id: 0 },
-            &[remote_ctrl_addr],
-            local_ctrl_addr,
-            Some(Duration::from_secs(10)),
-            1,
-        );
-        let (tx_payload, rx_payload) = bounded::<LinearOwnedReusable<Payload>>(8192);
-        let (tx_ddc, rx_ddc) = bounded::<LinearOwnedReusable<Vec<Complex<f32>>>>(8192);
-        let (tx_ddc_cmd, rx_ddc_cmd) = bounded::<DdcCmd>(32);
-        let (tx_recv_cmd, rx_recv_cmd) = bounded::<RecvCmd>(32);
-
-        tx_ddc_cmd
-            .send(DdcCmd::LoCh(N_PT_PER_FRAME as isize / 4))
-            .expect("failed to send loch");
-        let rx_thread = std::thread::spawn(|| recv_pkt(payload_socket.into(), tx_payload, rx_recv_cmd));
-        let ddc_thread = std::thread::spawn(move || {
-            let fir_coeffs = match smp_rate {
-                SdrSmpRate::SmpRate240 => fir_coeffs_full(),
-                SdrSmpRate::SmpRate120 => fir_coeffs_half(),
-            };
-            pkt_ddc(
-                rx_payload,
-                tx_ddc,
-                smp_rate.to_ndec(),
-                rx_ddc_cmd,
-                tx_recv_cmd,
-                &fir_coeffs,
-            );
-        });
+    ) -> Result<
+        (
+            Sdr,
+            Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+            Sender<DdcCmd>,
+        ),
+        SdaaError,
+    > {
+        SdrBuilder::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr)
+            .smp_rate(smp_rate)
+            .build()
+    }
 
+    /// Only listens to `source`, e.g. a replay, without a control
+    /// connection, see [`SdrBuilder::listen_only`]. Use
+    /// [`SdrBuilder::build_with_source`] to control a device as well.
+    #[allow(clippy::type_complexity)]
+    pub fn with_source<S: PacketSource + Send + 'static>(
+        source: S,
+        smp_rate: SdrSmpRate,
+    ) -> Result<
         (
-            Sdr {
-                rx_thread: Some(rx_thread),
-                ddc_thread: Some(ddc_thread),
-                ctrl: SdrCtrl {
-                    remote_ctrl_addr,
-                    local_ctrl_addr,
-                },
-            },
-            rx_ddc,
-            tx_ddc_cmd,
-        )
+            Sdr,
+            Receiver<LinearOwnedReusable<Block<Complex<f32>>>>,
+            Sender<DdcCmd>,
+        ),
+        SdaaError,
+    > {
+        // the payload address is only bound by `build`
+        let unbound = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        SdrBuilder::listen_only(unbound)
+            .smp_rate(smp_rate)
+            .build_with_source(source)
+    }
+}
+
+#[cfg(feature = "cuda")]
+impl Deref for Sdr {
+    type Target = SdrDevice;
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+#[cfg(feature = "cuda")]
+impl DerefMut for Sdr {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.device
     }
 }
 
 pub struct RawSdr {
     rx_thread: Option<JoinHandle<()>>,
-    pub ctrl: SdrCtrl,
+    device: SdrDevice,
 }
 
 impl Drop for RawSdr {
     fn drop(&mut self) {
         eprintln!("dropped");
-        self.ctrl.stream_stop();
+        if let Some(ctrl) = &self.device.ctrl {
+            ctrl.stream_stop();
+        }
         let h = self.rx_thread.take();
         if let Some(h1) = h
             && let Ok(()) = h1.join()
@@ -257,39 +1252,50 @@ impl Drop for RawSdr {
 }
 
 impl RawSdr {
+    /// Same as [`Sdr::new`] without the down conversion.
     #[allow(clippy::type_complexity)]
     pub fn new(
         remote_ctrl_addr: SocketAddrV4,
         local_ctrl_addr: SocketAddrV4,
         local_payload_addr: SocketAddrV4,
-    ) -> (
-        RawSdr,
-        Receiver<LinearOwnedReusable<Payload>>,
-        Sender<RecvCmd>,
-    ) {
-        let payload_socket =
-            UdpSocket::bind(local_payload_addr).expect("failed to bind payload socket");
-
-        send_cmd(
-            CtrlMsg::StreamStop { msg_id: 0 },
-            &[remote_ctrl_addr],
-            local_ctrl_addr,
-            Some(Duration::from_secs(10)),
-            1,
-        );
-        let (tx_payload, rx_payload) = bounded::<LinearOwnedReusable<Payload>>(8192);
-        let (tx_recv_cmd, rx_recv_cmd) = bounded::<RecvCmd>(32);
-        let rx_thread = std::thread::spawn(|| recv_pkt(payload_socket.into(), tx_payload, rx_recv_cmd));
+    ) -> Result<
         (
-            RawSdr {
-                rx_thread: Some(rx_thread),
-                ctrl: SdrCtrl {
-                    remote_ctrl_addr,
-                    local_ctrl_addr,
-                },
-            },
-            rx_payload,
-            tx_recv_cmd,
-        )
+            RawSdr,
+            Receiver<LinearOwnedReusable<Payload>>,
+            Sender<RecvCmd>,
+        ),
+        SdaaError,
+    > {
+        SdrBuilder::new(remote_ctrl_addr, local_ctrl_addr, local_payload_addr).build_raw()
+    }
+
+    /// Only listens to `source`, see [`Sdr::with_source`].
+    #[allow(clippy::type_complexity)]
+    pub fn with_source<S: PacketSource + Send + 'static>(
+        source: S,
+    ) -> Result<
+        (
+            RawSdr,
+            Receiver<LinearOwnedReusable<Payload>>,
+            Sender<RecvCmd>,
+        ),
+        SdaaError,
+    > {
+        // the payload address is only bound by `build_raw`
+        let unbound = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        SdrBuilder::listen_only(unbound).build_raw_with_source(source)
+    }
+}
+
+impl Deref for RawSdr {
+    type Target = SdrDevice;
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl DerefMut for RawSdr {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.device
     }
 }
@@ -0,0 +1,151 @@
+use std::{f64::consts::TAU, str::FromStr};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rand_distr::Normal;
+
+use crate::{RAW_SAMP_RATE, RawType};
+
+/// CW tone, `freq` in Hz, `amp` in ADC units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub freq: f64,
+    pub amp: f64,
+}
+
+/// Linear sweep from `f0` to `f1` Hz over `sweep` seconds, then repeated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chirp {
+    pub f0: f64,
+    pub f1: f64,
+    pub sweep: f64,
+    pub amp: f64,
+}
+
+fn parse_fields<const N: usize>(s: &str, what: &str) -> Result<[f64; N], String> {
+    let fields = s
+        .split(':')
+        .map(|x| x.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid {what} {s}: {e}"))?;
+    fields
+        .try_into()
+        .map_err(|_| format!("invalid {what} {s}, expected {N} fields separated by ':'"))
+}
+
+impl FromStr for Tone {
+    type Err = String;
+    /// `freq:amp`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [freq, amp] = parse_fields(s, "tone")?;
+        Ok(Tone { freq, amp })
+    }
+}
+
+impl FromStr for Chirp {
+    type Err = String;
+    /// `f0:f1:sweep:amp`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [f0, f1, sweep, amp] = parse_fields(s, "chirp")?;
+        if sweep <= 0.0 {
+            return Err(format!("invalid chirp {s}, sweep time must be positive"));
+        }
+        Ok(Chirp { f0, f1, sweep, amp })
+    }
+}
+
+/// How fast `fake_dev` produces frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pacing {
+    /// As fast as the consumers take them.
+    #[default]
+    FreeRunning,
+    /// At the rate the device would, [`RAW_SAMP_RATE`] samples per second.
+    RealTime,
+}
+
+/// What [`SignalGenerator`] synthesizes. The default is silence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignalConfig {
+    pub noise_rms: f64,
+    pub tones: Vec<Tone>,
+    pub chirps: Vec<Chirp>,
+    /// Clip at `±level` like a saturated ADC, otherwise only at the i16 range.
+    pub clip: Option<RawType>,
+}
+
+/// Produces a continuous i16 sample stream, frame by frame.
+pub struct SignalGenerator {
+    config: SignalConfig,
+    noise: Option<Normal<f64>>,
+    // phases in turns, kept in [0, 1) to bound rounding error
+    tone_phase: Vec<f64>,
+    chirp_phase: Vec<f64>,
+    chirp_time: Vec<f64>,
+    dt: f64,
+    // not the thread rng, so that the generator can move to another thread
+    rng: StdRng,
+}
+
+impl SignalGenerator {
+    pub fn new(config: SignalConfig) -> Self {
+        let noise = (config.noise_rms > 0.0)
+            .then(|| Normal::new(0.0, config.noise_rms).ok())
+            .flatten();
+        Self {
+            noise,
+            tone_phase: vec![0.0; config.tones.len()],
+            chirp_phase: vec![0.0; config.chirps.len()],
+            chirp_time: vec![0.0; config.chirps.len()],
+            dt: 1.0 / RAW_SAMP_RATE as f64,
+            rng: StdRng::from_os_rng(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &SignalConfig {
+        &self.config
+    }
+
+    /// True if every sample would be zero, so frames can be left as they are.
+    pub fn is_silent(&self) -> bool {
+        self.noise.is_none() && self.config.tones.is_empty() && self.config.chirps.is_empty()
+    }
+
+    /// Writes the next `data.len()` samples of the stream.
+    pub fn fill(&mut self, data: &mut [RawType]) {
+        if self.is_silent() {
+            data.fill(0);
+            return;
+        }
+        let (lo, hi) = match self.config.clip {
+            Some(level) => (-(level as f64), level as f64),
+            None => (RawType::MIN as f64, RawType::MAX as f64),
+        };
+        for x in data.iter_mut() {
+            let mut v = match self.noise {
+                Some(dist) => self.rng.sample(dist),
+                None => 0.0,
+            };
+            for (tone, phase) in self.config.tones.iter().zip(self.tone_phase.iter_mut()) {
+                v += tone.amp * (TAU * *phase).cos();
+                *phase = (*phase + tone.freq * self.dt).rem_euclid(1.0);
+            }
+            for ((chirp, phase), t) in self
+                .config
+                .chirps
+                .iter()
+                .zip(self.chirp_phase.iter_mut())
+                .zip(self.chirp_time.iter_mut())
+            {
+                v += chirp.amp * (TAU * *phase).cos();
+                let freq = chirp.f0 + (chirp.f1 - chirp.f0) * *t / chirp.sweep;
+                *phase = (*phase + freq * self.dt).rem_euclid(1.0);
+                *t += self.dt;
+                if *t >= chirp.sweep {
+                    *t -= chirp.sweep;
+                }
+            }
+            *x = v.round().clamp(lo, hi) as RawType;
+        }
+    }
+}
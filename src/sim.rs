@@ -0,0 +1,253 @@
+use std::{
+    fmt::Display,
+    net::{SocketAddr, UdpSocket},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossbeam::channel::{Receiver, Sender, TryRecvError, bounded};
+use rand::Rng;
+
+use crate::{
+    RAW_SAMP_RATE,
+    payload::{N_PT_PER_FRAME, Payload},
+    siggen::{Pacing, SignalConfig, SignalGenerator},
+    utils::as_u8_slice,
+};
+
+/// Packet rate of the real device.
+pub const REAL_TIME_PKT_RATE: f64 = RAW_SAMP_RATE as f64 / N_PT_PER_FRAME as f64;
+
+/// Holds frames back to a packet rate.
+pub(crate) struct Pacer {
+    rate: Option<f64>,
+    t0: Option<Instant>,
+}
+
+impl Pacer {
+    /// Paces to `rate` packets per second, `None` to not wait at all.
+    pub(crate) fn new(rate: Option<f64>) -> Self {
+        Self { rate, t0: None }
+    }
+
+    /// Paces to [`REAL_TIME_PKT_RATE`] for [`Pacing::RealTime`].
+    pub(crate) fn with_pacing(pacing: Pacing) -> Self {
+        Self::new((pacing == Pacing::RealTime).then_some(REAL_TIME_PKT_RATE))
+    }
+
+    /// Waits until `offset` after the first frame.
+    pub(crate) fn wait_until(&mut self, offset: Duration) {
+        if self.rate.is_none() {
+            return;
+        }
+        let t0 = *self.t0.get_or_insert_with(Instant::now);
+        let due = t0 + offset;
+        let now = Instant::now();
+        // sleeping per frame is too coarse, only catch up once well ahead
+        if due > now + Duration::from_millis(1) {
+            std::thread::sleep(due - now);
+        }
+    }
+
+    /// Waits until frame `n` is due at the rate.
+    pub(crate) fn wait_frame(&mut self, n: u64) {
+        if let Some(rate) = self.rate {
+            self.wait_until(Duration::from_secs_f64(n as f64 / rate));
+        }
+    }
+}
+
+/// Impairments and pacing of a [`PacketSimulator`]. Probabilities are per packet.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Packets per second, `None` to send as fast as possible.
+    pub rate: Option<f64>,
+    pub loss: f64,
+    /// Probability a packet is held back and sent up to `reorder_depth` packets later.
+    pub reorder: f64,
+    pub reorder_depth: usize,
+    pub duplicate: f64,
+    /// Restart `pkt_cnt` from 0 after this many packets, like a re-sync.
+    pub restart_every: Option<u64>,
+    /// Stop after this many packets.
+    pub npkts: Option<u64>,
+    pub signal: SignalConfig,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            rate: Some(REAL_TIME_PKT_RATE),
+            loss: 0.0,
+            reorder: 0.0,
+            reorder_depth: 8,
+            duplicate: 0.0,
+            restart_every: None,
+            npkts: None,
+            signal: SignalConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimStats {
+    pub generated: u64,
+    pub sent: u64,
+    pub lost: u64,
+    pub reordered: u64,
+    pub duplicated: u64,
+    pub restarts: u64,
+}
+
+impl Display for SimStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pkts generated {} sent {} lost {} reordered {} duplicated {} restarts",
+            self.generated, self.sent, self.lost, self.reordered, self.duplicated, self.restarts
+        )
+    }
+}
+
+/// Sends `Payload` datagrams the way the device does, with optional loss,
+/// reordering, duplication and `pkt_cnt` restarts.
+pub struct PacketSimulator {
+    config: SimConfig,
+    socket: UdpSocket,
+    target: SocketAddr,
+    generator: SignalGenerator,
+    stats: SimStats,
+}
+
+impl PacketSimulator {
+    pub fn new(config: SimConfig, target: SocketAddr) -> std::io::Result<Self> {
+        let bind_addr: SocketAddr = if target.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(bind_addr)?;
+        Ok(Self {
+            generator: SignalGenerator::new(config.signal.clone()),
+            config,
+            socket,
+            target,
+            stats: SimStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> SimStats {
+        self.stats
+    }
+
+    fn send(&mut self, payload: &Payload) -> std::io::Result<()> {
+        self.socket.send_to(as_u8_slice(payload), self.target)?;
+        self.stats.sent += 1;
+        Ok(())
+    }
+
+    /// Sends until `npkts` is reached or anything arrives on `rx_stop`,
+    /// including it being disconnected.
+    pub fn run(&mut self, rx_stop: &Receiver<()>) -> std::io::Result<SimStats> {
+        let mut rng = rand::rng();
+        let mut payload = Box::<Payload>::default();
+        // (index of the packet to send it after, packet)
+        let mut held: Vec<(u64, Box<Payload>)> = Vec::new();
+        let mut pacer = Pacer::new(self.config.rate);
+        let mut pkt_cnt = 0;
+        for i in 0.. {
+            if self.config.npkts.is_some_and(|n| i >= n)
+                || !matches!(rx_stop.try_recv(), Err(TryRecvError::Empty))
+            {
+                break;
+            }
+            pacer.wait_frame(i);
+
+            if let Some(n) = self.config.restart_every
+                && i > 0
+                && i % n == 0
+            {
+                pkt_cnt = 0;
+                self.stats.restarts += 1;
+            }
+            payload.pkt_cnt = pkt_cnt;
+            pkt_cnt += 1;
+            self.generator.fill(&mut payload.data);
+            self.stats.generated += 1;
+
+            if rng.random_bool(self.config.loss.clamp(0.0, 1.0)) {
+                self.stats.lost += 1;
+            } else if self.config.reorder_depth > 0
+                && rng.random_bool(self.config.reorder.clamp(0.0, 1.0))
+            {
+                let delay = rng.random_range(1..=self.config.reorder_depth as u64);
+                let mut late = Box::<Payload>::default();
+                late.pkt_cnt = payload.pkt_cnt;
+                late.data.copy_from_slice(&payload.data);
+                held.push((i + delay, late));
+                self.stats.reordered += 1;
+            } else {
+                self.send(&payload)?;
+                if rng.random_bool(self.config.duplicate.clamp(0.0, 1.0)) {
+                    self.send(&payload)?;
+                    self.stats.duplicated += 1;
+                }
+            }
+
+            let mut k = 0;
+            while k < held.len() {
+                if held[k].0 <= i {
+                    let (_, p) = held.swap_remove(k);
+                    self.send(&p)?;
+                } else {
+                    k += 1;
+                }
+            }
+        }
+        for (_, p) in std::mem::take(&mut held) {
+            self.send(&p)?;
+        }
+        Ok(self.stats)
+    }
+}
+
+/// A [`PacketSimulator`] running on its own thread, stopped on drop.
+pub struct SimHandle {
+    tx_stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<std::io::Result<SimStats>>>,
+}
+
+impl SimHandle {
+    /// Errors setting up the socket are returned by [`stop`](Self::stop).
+    pub fn spawn(config: SimConfig, target: SocketAddr) -> Self {
+        let (tx_stop, rx_stop) = bounded(1);
+        let thread =
+            std::thread::spawn(move || PacketSimulator::new(config, target)?.run(&rx_stop));
+        Self {
+            tx_stop: Some(tx_stop),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|h| h.is_finished())
+    }
+
+    pub fn stop(mut self) -> std::io::Result<SimStats> {
+        self.stop_and_join()
+    }
+
+    fn stop_and_join(&mut self) -> std::io::Result<SimStats> {
+        drop(self.tx_stop.take());
+        match self.thread.take() {
+            Some(h) => h.join().unwrap_or_else(|_| Err(std::io::Error::other("simulator panicked"))),
+            None => Ok(SimStats::default()),
+        }
+    }
+}
+
+impl Drop for SimHandle {
+    fn drop(&mut self) {
+        let _ = self.stop_and_join();
+    }
+}
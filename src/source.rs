@@ -0,0 +1,322 @@
+use std::{
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    payload::Payload,
+    pipeline::PacketSource,
+    siggen::{Pacing, SignalConfig, SignalGenerator},
+    sim::Pacer,
+    utils::{as_mut_u8_slice, as_u8_slice},
+};
+
+fn end_of_stream() -> Error {
+    Error::from(ErrorKind::UnexpectedEof)
+}
+
+/// Copies `frame` into `buf`, returning the full frame size like a socket
+/// reports the datagram size.
+fn deliver(frame: &Payload, buf: &mut [u8]) -> usize {
+    let bytes = as_u8_slice(frame);
+    let n = bytes.len().min(buf.len());
+    buf[..n].copy_from_slice(&bytes[..n]);
+    bytes.len()
+}
+
+/// Layout of a file replayed by [`FileReplay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    /// Whole datagrams back to back, `size_of::<Payload>()` bytes each.
+    Datagrams,
+    /// Bare i16 samples as written by `dump_bb`, framed with consecutive
+    /// `pkt_cnt` starting at `first_pkt`.
+    Samples { first_pkt: u64 },
+}
+
+/// Plays a recorded stream back from a file. At the end of the file the
+/// stream ends, or starts over with `pkt_cnt` restarting if repeated.
+pub struct FileReplay {
+    reader: BufReader<File>,
+    format: ReplayFormat,
+    pacer: Pacer,
+    repeat: bool,
+    frame: Box<Payload>,
+    n_frames: u64,
+    pkt_cnt: u64,
+}
+
+impl FileReplay {
+    pub fn open(path: impl AsRef<Path>, format: ReplayFormat, pacing: Pacing) -> std::io::Result<Self> {
+        let pkt_cnt = match format {
+            ReplayFormat::Datagrams => 0,
+            ReplayFormat::Samples { first_pkt } => first_pkt,
+        };
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            format,
+            pacer: Pacer::with_pacing(pacing),
+            repeat: false,
+            frame: Box::default(),
+            n_frames: 0,
+            pkt_cnt,
+        })
+    }
+
+    /// Starts over at the end of the file instead of ending the stream.
+    pub fn repeat(mut self) -> Self {
+        self.repeat = true;
+        self
+    }
+
+    /// Reads the next frame, a trailing partial frame counts as the end.
+    fn read_frame(&mut self) -> std::io::Result<()> {
+        let dst = match self.format {
+            ReplayFormat::Datagrams => as_mut_u8_slice(&mut *self.frame),
+            ReplayFormat::Samples { .. } => as_mut_u8_slice(&mut self.frame.data),
+        };
+        self.reader.read_exact(dst)?;
+        if let ReplayFormat::Samples { .. } = self.format {
+            self.frame.pkt_cnt = self.pkt_cnt;
+            self.pkt_cnt += 1;
+        }
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> std::io::Result<()> {
+        self.reader.seek(SeekFrom::Start(0))?;
+        if let ReplayFormat::Samples { first_pkt } = self.format {
+            self.pkt_cnt = first_pkt;
+        }
+        Ok(())
+    }
+}
+
+impl PacketSource for FileReplay {
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.pacer.wait_frame(self.n_frames);
+        match self.read_frame() {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && self.repeat => {
+                self.rewind()?;
+                // an empty file still ends the stream
+                self.read_frame()?;
+            }
+            Err(e) => return Err(e),
+        }
+        self.n_frames += 1;
+        Ok(deliver(&self.frame, buf))
+    }
+}
+
+const PCAP_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IPPROTO_UDP: u8 = 17;
+
+/// Plays back the UDP datagrams of a capture in the classic pcap format, as
+/// written by `tcpdump -w`. IPv4 over Ethernet, Linux cooked and raw IP
+/// captures are understood, pcapng is not. Fragmented datagrams and
+/// everything that is not UDP are skipped. The capture times are returned
+/// as receive timestamps.
+pub struct PcapReplay {
+    reader: BufReader<File>,
+    big_endian: bool,
+    nanos: bool,
+    linktype: u32,
+    dst_port: Option<u16>,
+    pacer: Pacer,
+    first_stamp: Option<DateTime<Utc>>,
+    record: Vec<u8>,
+}
+
+impl PcapReplay {
+    pub fn open(path: impl AsRef<Path>, pacing: Pacing) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0_u8; PCAP_HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let (big_endian, nanos) = match u32::from_le_bytes([header[0], header[1], header[2], header[3]]) {
+            0xa1b2c3d4 => (false, false),
+            0xd4c3b2a1 => (true, false),
+            0xa1b23c4d => (false, true),
+            0x4d3cb2a1 => (true, true),
+            magic => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("not a pcap file, magic {magic:#x}"),
+                ));
+            }
+        };
+        let mut source = Self {
+            reader,
+            big_endian,
+            nanos,
+            linktype: 0,
+            dst_port: None,
+            pacer: Pacer::with_pacing(pacing),
+            first_stamp: None,
+            record: Vec::new(),
+        };
+        source.linktype = source.u32_at(&header, 20);
+        match source.linktype {
+            LINKTYPE_ETHERNET | LINKTYPE_RAW | LINKTYPE_LINUX_SLL | LINKTYPE_IPV4 => Ok(source),
+            l => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported pcap link type {l}"),
+            )),
+        }
+    }
+
+    /// Only plays back datagrams sent to `port`.
+    pub fn dst_port(mut self, port: u16) -> Self {
+        self.dst_port = Some(port);
+        self
+    }
+
+    fn u32_at(&self, b: &[u8], offset: usize) -> u32 {
+        let x = [b[offset], b[offset + 1], b[offset + 2], b[offset + 3]];
+        if self.big_endian {
+            u32::from_be_bytes(x)
+        } else {
+            u32::from_le_bytes(x)
+        }
+    }
+
+    /// Reads the next record into `self.record`, returning its capture time.
+    fn read_record(&mut self) -> std::io::Result<DateTime<Utc>> {
+        let mut header = [0_u8; PCAP_RECORD_HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let secs = self.u32_at(&header, 0);
+        let frac = self.u32_at(&header, 4);
+        let incl_len = self.u32_at(&header, 8) as usize;
+        self.record.resize(incl_len, 0);
+        self.reader.read_exact(&mut self.record)?;
+        let nanos = if self.nanos { frac } else { frac.saturating_mul(1000) };
+        DateTime::from_timestamp(secs as i64, nanos)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid pcap timestamp"))
+    }
+
+    /// Range of the UDP payload in `self.record`, `None` if the record is
+    /// not an unfragmented UDP/IPv4 datagram to the selected port.
+    fn udp_payload(&self) -> Option<std::ops::Range<usize>> {
+        let r = &self.record;
+        let be16 = |i: usize| Some(u16::from_be_bytes([*r.get(i)?, *r.get(i + 1)?]));
+        let ip = match self.linktype {
+            LINKTYPE_ETHERNET => {
+                let mut offset = 12;
+                let mut ethertype = be16(offset)?;
+                while ethertype == ETHERTYPE_VLAN {
+                    offset += 4;
+                    ethertype = be16(offset)?;
+                }
+                (ethertype == ETHERTYPE_IPV4).then_some(offset + 2)?
+            }
+            LINKTYPE_LINUX_SLL => (be16(14)? == ETHERTYPE_IPV4).then_some(16)?,
+            _ => 0,
+        };
+        let version_ihl = *r.get(ip)?;
+        if version_ihl >> 4 != 4 || r.get(ip + 9) != Some(&IPPROTO_UDP) {
+            return None;
+        }
+        // more fragments set or a fragment offset
+        if be16(ip + 6)? & 0x3fff != 0 {
+            return None;
+        }
+        let udp = ip + (version_ihl & 0x0f) as usize * 4;
+        if self.dst_port.is_some_and(|p| be16(udp + 2) != Some(p)) {
+            return None;
+        }
+        let len = (be16(udp + 4)? as usize).checked_sub(8)?;
+        let start = udp + 8;
+        if start > r.len() {
+            return None;
+        }
+        // truncated by the snap length, the validator will reject it
+        Some(start..(start + len).min(r.len()))
+    }
+}
+
+impl PacketSource for PcapReplay {
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.recv_timestamped(buf).map(|(s, _)| s)
+    }
+
+    fn enable_timestamps(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn recv_timestamped(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, Option<DateTime<Utc>>)> {
+        loop {
+            let stamp = match self.read_record() {
+                Ok(stamp) => stamp,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(end_of_stream()),
+                Err(e) => return Err(e),
+            };
+            let Some(range) = self.udp_payload() else {
+                continue;
+            };
+            let first = *self.first_stamp.get_or_insert(stamp);
+            self.pacer
+                .wait_until((stamp - first).to_std().unwrap_or_default());
+            let data = &self.record[range];
+            let n = data.len().min(buf.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            return Ok((data.len(), Some(stamp)));
+        }
+    }
+}
+
+/// Frames synthesized by a [`SignalGenerator`] with consecutive `pkt_cnt`,
+/// to run the pipeline without hardware. Unlike `fake_dev` they go through
+/// the receiver like real packets.
+pub struct GeneratorSource {
+    generator: SignalGenerator,
+    pacer: Pacer,
+    frame: Box<Payload>,
+    npkts: Option<u64>,
+    n_frames: u64,
+}
+
+impl GeneratorSource {
+    pub fn new(signal: SignalConfig, pacing: Pacing) -> Self {
+        Self {
+            generator: SignalGenerator::new(signal),
+            pacer: Pacer::with_pacing(pacing),
+            frame: Box::default(),
+            npkts: None,
+            n_frames: 0,
+        }
+    }
+
+    /// Ends the stream after `npkts` frames.
+    pub fn limit(mut self, npkts: u64) -> Self {
+        self.npkts = Some(npkts);
+        self
+    }
+}
+
+impl PacketSource for GeneratorSource {
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.npkts.is_some_and(|n| self.n_frames >= n) {
+            return Err(end_of_stream());
+        }
+        self.pacer.wait_frame(self.n_frames);
+        self.frame.pkt_cnt = self.n_frames;
+        self.generator.fill(&mut self.frame.data);
+        self.n_frames += 1;
+        Ok(deliver(&self.frame, buf))
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    fmt::Display,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{
+    arrival::ArrivalSummary,
+    payload::{MalformedCounters, MalformedSnapshot},
+};
+
+/// Number of burst-length bins. Bin `i` counts gaps of `2^i ..= 2^(i+1)-1`
+/// consecutive lost packets, the last bin also counts everything longer.
+pub const N_BURST_BINS: usize = 16;
+
+/// Receive counters updated by `recv_pkt` and shared with its owner.
+#[derive(Debug, Default)]
+pub struct RecvStats {
+    pub received: AtomicU64,
+    pub gap_filled: AtomicU64,
+    pub late: AtomicU64,
+    pub duplicate: AtomicU64,
+    pub queue_full: AtomicU64,
+    /// Times `pkt_cnt` jumped back while streaming.
+    pub restarts: AtomicU64,
+    pub malformed: MalformedCounters,
+    pub burst_hist: [AtomicU64; N_BURST_BINS],
+    /// Only filled in when receive timestamps are enabled.
+    pub arrival: Mutex<Option<ArrivalSummary>>,
+    /// `version` of the first accepted payload.
+    pub payload_version: OnceLock<u32>,
+}
+
+impl RecvStats {
+    pub fn record_burst(&self, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let bin = (len.ilog2() as usize).min(N_BURST_BINS - 1);
+        self.burst_hist[bin].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn arrival(&self) -> Option<ArrivalSummary> {
+        *self.arrival.lock().unwrap()
+    }
+
+    pub fn payload_version(&self) -> Option<u32> {
+        self.payload_version.get().copied()
+    }
+
+    pub fn snapshot(&self) -> RecvStatsSnapshot {
+        let received = self.received.load(Ordering::Relaxed);
+        let gap_filled = self.gap_filled.load(Ordering::Relaxed);
+        let total = received + gap_filled;
+        RecvStatsSnapshot {
+            received,
+            gap_filled,
+            late: self.late.load(Ordering::Relaxed),
+            duplicate: self.duplicate.load(Ordering::Relaxed),
+            malformed: self.malformed.snapshot(),
+            queue_full: self.queue_full.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            loss_ratio: if total == 0 {
+                0.0
+            } else {
+                gap_filled as f64 / total as f64
+            },
+            burst_hist: std::array::from_fn(|i| self.burst_hist[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Plain copy of [`RecvStats`] at one point in time.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecvStatsSnapshot {
+    pub received: u64,
+    pub gap_filled: u64,
+    pub late: u64,
+    pub duplicate: u64,
+    pub malformed: MalformedSnapshot,
+    pub queue_full: u64,
+    pub restarts: u64,
+    pub loss_ratio: f64,
+    pub burst_hist: [u64; N_BURST_BINS],
+}
+
+impl Display for RecvStatsSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pkts received {} filled {} late {} dup {} malformed (len {} hdr {} ver {} size {}) {} stalls {} restarts loss={:e}",
+            self.received,
+            self.gap_filled,
+            self.late,
+            self.duplicate,
+            self.malformed.total(),
+            self.malformed.length,
+            self.malformed.header,
+            self.malformed.version,
+            self.malformed.frame_size,
+            self.queue_full,
+            self.restarts,
+            self.loss_ratio
+        )
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+use sdaa_ctrl::ctrl_msg::CtrlMsg;
+
+use crate::transport::CmdReplies;
+
+/// `trans_state` bit set once the device is powered up by `PwrCtrl`.
+pub const TRANS_STATE_AWAKE: u32 = 0b10;
+
+/// `locked` the device reports once it is ready for `Init`, possibly with
+/// [`LOCK_OPTIONAL`] set as well. Taken from what the devices answer, the
+/// meaning of the individual bits is not documented.
+pub const LOCK_REQUIRED: u32 = 0x2f;
+/// `locked` bit that may or may not be set on a ready device.
+pub const LOCK_OPTIONAL: u32 = 0x10;
+
+/// The individual bits of `locked`.
+///
+/// Which PLL or ADC each bit stands for is documented neither by the
+/// firmware nor by sdaa_ctrl, so named lock indicators are not available
+/// yet: the bits are only named by position in [`NAMES`](Self::NAMES) and
+/// judged as a whole against [`LOCK_REQUIRED`]. Rename them once the
+/// assignment is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockState {
+    pub raw: u32,
+}
+
+impl LockState {
+    pub const NAMES: [&str; 6] = ["bit0", "bit1", "bit2", "bit3", "bit4", "bit5"];
+
+    pub fn new(raw: u32) -> Self {
+        Self { raw }
+    }
+
+    pub fn bit(&self, i: u32) -> bool {
+        self.raw & (1 << i) != 0
+    }
+
+    /// [`LOCK_REQUIRED`] set, [`LOCK_OPTIONAL`] either way and nothing else.
+    pub fn is_locked(&self) -> bool {
+        self.raw & !LOCK_OPTIONAL == LOCK_REQUIRED
+    }
+
+    pub fn bits(&self) -> [bool; 6] {
+        std::array::from_fn(|i| self.bit(i as u32))
+    }
+}
+
+/// Decoded `QueryReply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceStatus {
+    pub fm_ver: u32,
+    /// `tick_cnt2` and `tick_cnt1` as one counter.
+    pub ticks: u64,
+    pub trans_state: u32,
+    pub locked: LockState,
+    /// Raw `health` word. Its meaning is not documented, so it is passed
+    /// on as is.
+    pub health: u32,
+}
+
+impl DeviceStatus {
+    pub fn from_reply(msg: &CtrlMsg) -> Option<Self> {
+        if let CtrlMsg::QueryReply {
+            fm_ver,
+            tick_cnt1,
+            tick_cnt2,
+            trans_state,
+            locked,
+            health,
+            ..
+        } = *msg
+        {
+            Some(Self {
+                fm_ver,
+                ticks: ((tick_cnt2 as u64) << 32) | tick_cnt1 as u64,
+                trans_state,
+                locked: LockState::new(locked),
+                health,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Status from the only normal reply of a query, `None` if there is not exactly one.
+    pub fn from_replies(replies: &CmdReplies) -> Option<Self> {
+        match replies.normal.as_slice() {
+            [(_, status)] => *status,
+            _ => None,
+        }
+    }
+
+    pub fn is_awake(&self) -> bool {
+        self.trans_state & TRANS_STATE_AWAKE != 0
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_locked()
+    }
+
+    /// Awake and locked, i.e. ready for `Init`.
+    pub fn is_ready(&self) -> bool {
+        self.is_awake() && self.is_locked()
+    }
+}
+
+impl Display for DeviceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "firmware:    {:#x}", self.fm_ver)?;
+        writeln!(
+            f,
+            "trans state: {:#x} ({})",
+            self.trans_state,
+            if self.is_awake() { "awake" } else { "off" }
+        )?;
+        write!(
+            f,
+            "locked:      {:#x} ({})",
+            self.locked.raw,
+            if self.is_locked() { "ok" } else { "not locked" }
+        )?;
+        for (name, bit) in LockState::NAMES.iter().zip(self.locked.bits()) {
+            write!(f, "\n  {name:<11}{}", bit as u8)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "health:      {:#x}", self.health)?;
+        write!(f, "ticks:       {}", self.ticks)
+    }
+}
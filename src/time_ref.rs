@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{RAW_SAMP_RATE, payload::N_PT_PER_FRAME};
+
+/// MJD of the Unix epoch.
+pub const MJD_UNIX_EPOCH: f64 = 40587.0;
+
+/// Assumed bound on network and queueing delay when the epoch has to be
+/// guessed from packet arrival, see [`TimeReference::from_arrival`].
+pub const ARRIVAL_LATENCY: Duration = Duration::from_millis(10);
+
+/// Relates the raw sample counter of the stream to UTC. `pkt_cnt` counts
+/// frames of [`N_PT_PER_FRAME`] samples at `samp_rate` since the last sync,
+/// so one epoch is enough to time tag every sample after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeReference {
+    /// UTC of raw sample 0, i.e. of the sync.
+    pub epoch: DateTime<Utc>,
+    /// Half width of the interval `epoch` is known to lie in.
+    pub uncertainty: Duration,
+    /// Raw samples per second, nominally [`RAW_SAMP_RATE`].
+    pub samp_rate: f64,
+}
+
+impl TimeReference {
+    pub fn new(epoch: DateTime<Utc>, uncertainty: Duration) -> Self {
+        Self {
+            epoch,
+            uncertainty,
+            samp_rate: RAW_SAMP_RATE as f64,
+        }
+    }
+
+    /// Epoch from the send and reply times of the sync command.
+    pub fn from_sync(sent: DateTime<Utc>, replied: DateTime<Utc>) -> Self {
+        let half_rtt = (replied - sent) / 2;
+        Self::new(
+            sent + half_rtt,
+            half_rtt.to_std().unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Epoch extrapolated back from the arrival of a packet. `latency` bounds
+    /// the network and buffering delay, which is not known here.
+    pub fn from_arrival(pkt_cnt: u64, arrival: DateTime<Utc>, latency: Duration) -> Self {
+        let tref = Self::new(arrival, latency);
+        let offset = tref.sample_offset(pkt_cnt * N_PT_PER_FRAME as u64);
+        Self { epoch: arrival - offset, ..tref }
+    }
+
+    fn sample_offset(&self, sample_index: u64) -> TimeDelta {
+        let ns = sample_index as f64 * 1e9 / self.samp_rate;
+        TimeDelta::nanoseconds(ns.round() as i64)
+    }
+
+    pub fn sample_time(&self, sample_index: u64) -> DateTime<Utc> {
+        self.epoch + self.sample_offset(sample_index)
+    }
+
+    pub fn pkt_time(&self, pkt_cnt: u64) -> DateTime<Utc> {
+        self.sample_time(pkt_cnt * N_PT_PER_FRAME as u64)
+    }
+
+    /// Index of the first sample taken at or after `t`, 0 if `t` is before the epoch.
+    pub fn sample_index_at(&self, t: DateTime<Utc>) -> u64 {
+        let Some(ns) = (t - self.epoch).num_nanoseconds() else {
+            return if t > self.epoch { u64::MAX } else { 0 };
+        };
+        if ns <= 0 {
+            return 0;
+        }
+        (ns as f64 * self.samp_rate / 1e9).ceil() as u64
+    }
+
+    pub fn sample_mjd(&self, sample_index: u64) -> f64 {
+        to_mjd(self.sample_time(sample_index))
+    }
+
+    pub fn pkt_mjd(&self, pkt_cnt: u64) -> f64 {
+        to_mjd(self.pkt_time(pkt_cnt))
+    }
+}
+
+pub fn to_mjd(t: DateTime<Utc>) -> f64 {
+    MJD_UNIX_EPOCH
+        + (t.timestamp() as f64 + t.timestamp_subsec_nanos() as f64 * 1e-9) / 86400.0
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::VecDeque,
+    mem::{Discriminant, discriminant},
+    net::{SocketAddr, SocketAddrV4},
+    sync::Mutex,
+    time::Duration,
+};
+
+use sdaa_ctrl::ctrl_msg::{CmdReplySummary, CtrlMsg, send_cmd};
+
+use crate::status::DeviceStatus;
+
+/// Carries control commands to devices and collects their replies.
+pub trait CtrlTransport {
+    /// Sends `cmd` once to every address in `remote` and waits up to
+    /// `timeout` for the replies.
+    fn exchange(&self, cmd: CtrlMsg, remote: &[SocketAddrV4], timeout: Duration) -> CmdReplies;
+}
+
+/// Normal replies to one command, query replies already decoded. Of the
+/// `CmdReplySummary` of sdaa_ctrl only `normal_reply` is relied on, so a
+/// remote missing here either did not reply or replied abnormally.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CmdReplies {
+    pub normal: Vec<(SocketAddr, Option<DeviceStatus>)>,
+}
+
+impl CmdReplies {
+    pub fn replied(&self, remote: SocketAddrV4) -> bool {
+        self.normal.iter().any(|(a, _)| *a == SocketAddr::V4(remote))
+    }
+
+    /// Decoded query reply of `remote`, `None` if it sent none.
+    pub fn status(&self, remote: SocketAddrV4) -> Option<DeviceStatus> {
+        self.normal
+            .iter()
+            .find(|(a, _)| *a == SocketAddr::V4(remote))
+            .and_then(|(_, status)| *status)
+    }
+}
+
+impl From<CmdReplySummary> for CmdReplies {
+    fn from(summary: CmdReplySummary) -> Self {
+        Self {
+            normal: summary
+                .normal_reply
+                .into_iter()
+                .map(|(addr, msg)| (addr, DeviceStatus::from_reply(&msg)))
+                .collect(),
+        }
+    }
+}
+
+/// Kind of a command, what an [`Exchange`] is recorded and replayed by.
+pub type CmdKind = Discriminant<CtrlMsg>;
+
+/// A copy of `cmd` to send again. `CtrlMsg` is not known to be `Clone`,
+/// so only the commands this crate sends are rebuilt, others go out once.
+pub(crate) fn copy_cmd(cmd: &CtrlMsg) -> Option<CtrlMsg> {
+    Some(match *cmd {
+        CtrlMsg::Query { msg_id } => CtrlMsg::Query { msg_id },
+        CtrlMsg::PwrCtrl { msg_id, op_code } => CtrlMsg::PwrCtrl { msg_id, op_code },
+        CtrlMsg::Init {
+            msg_id,
+            reserved_zeros,
+        } => CtrlMsg::Init {
+            msg_id,
+            reserved_zeros,
+        },
+        CtrlMsg::Sync { msg_id } => CtrlMsg::Sync { msg_id },
+        CtrlMsg::StreamStart { msg_id } => CtrlMsg::StreamStart { msg_id },
+        CtrlMsg::StreamStop { msg_id } => CtrlMsg::StreamStop { msg_id },
+        _ => return None,
+    })
+}
+
+/// How [`SdrCtrl`](crate::sdr::SdrCtrl) repeats commands that got no reply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub timeout: Duration,
+    /// Attempts after the first one.
+    pub retries: usize,
+    /// Wait before the first retry.
+    pub backoff: Duration,
+    /// Factor the wait grows by with every further retry.
+    pub backoff_factor: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            retries: 1,
+            backoff: Duration::from_millis(100),
+            backoff_factor: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        self.backoff
+            .mul_f64(self.backoff_factor.max(1.0).powi(attempt.saturating_sub(1) as i32))
+    }
+}
+
+/// The real device, over UDP from `local_addr`.
+pub struct UdpTransport {
+    pub local_addr: SocketAddrV4,
+}
+
+impl UdpTransport {
+    pub fn new(local_addr: SocketAddrV4) -> Self {
+        Self { local_addr }
+    }
+}
+
+impl CtrlTransport for UdpTransport {
+    fn exchange(&self, cmd: CtrlMsg, remote: &[SocketAddrV4], timeout: Duration) -> CmdReplies {
+        send_cmd(cmd, remote, self.local_addr, Some(timeout), 0).into()
+    }
+}
+
+/// One command sent through a transport and the replies to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub cmd: CmdKind,
+    pub remote: Vec<SocketAddrV4>,
+    pub replies: CmdReplies,
+}
+
+/// Passes commands on to another transport and keeps every exchange, to be
+/// played back later with [`ReplayTransport`].
+pub struct RecordingTransport<T> {
+    inner: T,
+    log: Mutex<Vec<Exchange>>,
+}
+
+impl<T: CtrlTransport> RecordingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            log: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn take_recording(&self) -> Vec<Exchange> {
+        std::mem::take(&mut *self.log.lock().unwrap())
+    }
+}
+
+impl<T: CtrlTransport> CtrlTransport for RecordingTransport<T> {
+    fn exchange(&self, cmd: CtrlMsg, remote: &[SocketAddrV4], timeout: Duration) -> CmdReplies {
+        let kind = discriminant(&cmd);
+        let replies = self.inner.exchange(cmd, remote, timeout);
+        self.log.lock().unwrap().push(Exchange {
+            cmd: kind,
+            remote: remote.to_vec(),
+            replies: replies.clone(),
+        });
+        replies
+    }
+}
+
+/// Answers from a recorded script of exchanges, in order. A command of a
+/// different kind than the recorded one, or one past the end of the script,
+/// gets no reply.
+pub struct ReplayTransport {
+    script: Mutex<VecDeque<Exchange>>,
+}
+
+impl ReplayTransport {
+    pub fn new(script: Vec<Exchange>) -> Self {
+        Self {
+            script: Mutex::new(script.into()),
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+}
+
+impl CtrlTransport for ReplayTransport {
+    fn exchange(&self, cmd: CtrlMsg, _remote: &[SocketAddrV4], _timeout: Duration) -> CmdReplies {
+        let mut script = self.script.lock().unwrap();
+        if script.front().is_some_and(|e| e.cmd == discriminant(&cmd))
+            && let Some(exchange) = script.pop_front()
+        {
+            return exchange.replies;
+        }
+        eprintln!("replay: unexpected command, {} exchanges left", script.len());
+        CmdReplies::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sdr::{DeviceError, DeviceState, DeviceStep, SdrCtrl},
+        status::LockState,
+    };
+
+    /// A ready board that ignores the first `lost` commands.
+    struct LossyBoard {
+        lost: Mutex<usize>,
+    }
+
+    impl CtrlTransport for LossyBoard {
+        fn exchange(&self, cmd: CtrlMsg, remote: &[SocketAddrV4], _timeout: Duration) -> CmdReplies {
+            let mut lost = self.lost.lock().unwrap();
+            if *lost > 0 {
+                *lost -= 1;
+                return CmdReplies::default();
+            }
+            let status = matches!(cmd, CtrlMsg::Query { .. }).then_some(DeviceStatus {
+                fm_ver: 0,
+                ticks: 0,
+                trans_state: crate::status::TRANS_STATE_AWAKE,
+                locked: LockState::new(crate::status::LOCK_REQUIRED),
+                health: 0,
+            });
+            CmdReplies {
+                normal: remote.iter().map(|&r| (r.into(), status)).collect(),
+            }
+        }
+    }
+
+    const REMOTE: SocketAddrV4 = SocketAddrV4::new(std::net::Ipv4Addr::LOCALHOST, 3000);
+
+    fn ctrl(lost: usize, retries: usize) -> SdrCtrl<RecordingTransport<LossyBoard>> {
+        let board = LossyBoard {
+            lost: Mutex::new(lost),
+        };
+        let retry = RetryPolicy {
+            retries,
+            backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        SdrCtrl::with_transport(REMOTE, RecordingTransport::new(board), retry)
+    }
+
+    fn kinds(recording: &[Exchange]) -> Vec<CmdKind> {
+        recording.iter().map(|e| e.cmd).collect()
+    }
+
+    #[test]
+    fn lost_reply_is_retried() {
+        let ctrl = ctrl(1, 1);
+        assert!(matches!(ctrl.refresh_state(), Ok(DeviceState::Locked)));
+        assert_eq!(ctrl.state(), DeviceState::Locked);
+
+        let stop = discriminant(&CtrlMsg::StreamStop { msg_id: 0 });
+        let query = discriminant(&CtrlMsg::Query { msg_id: 0 });
+        let recording = ctrl.transport().take_recording();
+        assert_eq!(kinds(&recording), [stop, stop, query]);
+        assert!(!recording[0].replies.replied(REMOTE));
+        assert!(recording[1].replies.replied(REMOTE));
+        assert!(recording[2].replies.status(REMOTE).is_some_and(|s| s.is_ready()));
+    }
+
+    #[test]
+    fn silent_device_faults_and_needs_refresh() {
+        let ctrl = ctrl(usize::MAX, 2);
+        assert!(matches!(
+            ctrl.power_up(),
+            Err(DeviceError::NoReply(DeviceStep::Wakeup))
+        ));
+        assert_eq!(ctrl.state(), DeviceState::Faulted);
+        assert_eq!(ctrl.transport().take_recording().len(), 3);
+
+        assert!(matches!(
+            ctrl.initialize(),
+            Err(DeviceError::InvalidTransition {
+                from: DeviceState::Faulted,
+                step: DeviceStep::Init,
+            })
+        ));
+        assert!(ctrl.transport().take_recording().is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    alloc::{Layout, alloc_zeroed, dealloc},
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    os::{
+        fd::AsRawFd,
+        unix::fs::{FileExt, OpenOptionsExt},
+    },
+    path::Path,
+    time::Duration,
+};
+
+use io_uring::{IoUring, cqueue, opcode, squeue, types};
+
+use crate::{
+    payload::Payload,
+    pipeline::{MaybeMulticastReceiver, PacketSource},
+};
+
+/// O_DIRECT requires buffers, lengths and file offsets aligned to the logical block size.
+pub const DIRECT_IO_ALIGN: usize = 4096;
+
+const RECV_BGID: u16 = 0;
+const RECV_USER_DATA: u64 = u64::MAX;
+const PROVIDE_USER_DATA: u64 = u64::MAX - 1;
+
+struct AlignedBuf {
+    ptr: *mut u8,
+    len: usize,
+}
+
+unsafe impl Send for AlignedBuf {}
+
+impl AlignedBuf {
+    fn new(len: usize) -> Self {
+        let layout = Layout::from_size_align(len, DIRECT_IO_ALIGN).expect("invalid buffer layout");
+        let ptr = unsafe { alloc_zeroed(layout) };
+        assert!(!ptr.is_null(), "failed to allocate aligned buffer");
+        Self { ptr, len }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        let layout =
+            Layout::from_size_align(self.len, DIRECT_IO_ALIGN).expect("invalid buffer layout");
+        unsafe { dealloc(self.ptr, layout) };
+    }
+}
+
+fn push_sqe(ring: &mut IoUring, entry: &squeue::Entry) -> std::io::Result<()> {
+    loop {
+        if unsafe { ring.submission().push(entry) }.is_ok() {
+            return Ok(());
+        }
+        ring.submit()?;
+    }
+}
+
+/// Packet source that receives datagrams with a multishot `recv` into a ring
+/// of kernel-provided buffers, so a burst of packets costs one syscall.
+pub struct UringReceiver {
+    ring: IoUring,
+    bufs: AlignedBuf,
+    buf_size: usize,
+    armed: bool,
+    timeout: Duration,
+    socket: MaybeMulticastReceiver,
+}
+
+impl UringReceiver {
+    pub fn new(socket: MaybeMulticastReceiver, nbufs: u16) -> std::io::Result<Self> {
+        assert!(nbufs > 0);
+        // some slack per buffer so that oversized datagrams show up with a wrong length
+        let buf_size = std::mem::size_of::<Payload>() + 64;
+        let mut ring = IoUring::builder()
+            .setup_cqsize((2 * nbufs as u32).max(512))
+            .build(256)?;
+        let bufs = AlignedBuf::new(buf_size * nbufs as usize);
+        let provide = opcode::ProvideBuffers::new(bufs.ptr, buf_size as i32, nbufs, RECV_BGID, 0)
+            .build()
+            .user_data(PROVIDE_USER_DATA);
+        push_sqe(&mut ring, &provide)?;
+        ring.submit()?;
+
+        Ok(Self {
+            ring,
+            bufs,
+            buf_size,
+            armed: false,
+            timeout: Duration::from_secs(1),
+            socket,
+        })
+    }
+
+    fn recycle(&mut self, bid: u16) -> std::io::Result<()> {
+        let addr = unsafe { self.bufs.ptr.add(bid as usize * self.buf_size) };
+        let provide = opcode::ProvideBuffers::new(addr, self.buf_size as i32, 1, RECV_BGID, bid)
+            .build()
+            .user_data(PROVIDE_USER_DATA);
+        push_sqe(&mut self.ring, &provide)
+    }
+}
+
+impl PacketSource for UringReceiver {
+    fn recv_into(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let cqe = self.ring.completion().next();
+            let Some(cqe) = cqe else {
+                if !self.armed {
+                    let recv =
+                        opcode::RecvMulti::new(types::Fd(self.socket.as_raw_fd()), RECV_BGID)
+                            .build()
+                            .user_data(RECV_USER_DATA);
+                    push_sqe(&mut self.ring, &recv)?;
+                    self.armed = true;
+                }
+                let ts = types::Timespec::from(self.timeout);
+                let args = types::SubmitArgs::new().timespec(&ts);
+                match self.ring.submitter().submit_with_args(1, &args) {
+                    Err(e) if e.raw_os_error() == Some(libc::ETIME) => {
+                        return Err(Error::from(ErrorKind::TimedOut));
+                    }
+                    Err(e) if e.raw_os_error() == Some(libc::EINTR) => continue,
+                    Err(e) => return Err(e),
+                    Ok(_) => continue,
+                }
+            };
+
+            let result = cqe.result();
+            if cqe.user_data() == PROVIDE_USER_DATA {
+                if result < 0 {
+                    return Err(Error::from_raw_os_error(-result));
+                }
+                continue;
+            }
+
+            if !cqueue::more(cqe.flags()) {
+                self.armed = false;
+            }
+            if result < 0 {
+                if -result == libc::ENOBUFS {
+                    // consumer fell behind, the multishot request is re-armed above
+                    continue;
+                }
+                return Err(Error::from_raw_os_error(-result));
+            }
+
+            let bid = cqueue::buffer_select(cqe.flags()).expect("recv completed without a buffer");
+            let start = bid as usize * self.buf_size;
+            let n = (result as usize).min(buf.len());
+            buf[..n].copy_from_slice(&self.bufs.as_slice()[start..start + n]);
+            self.recycle(bid)?;
+            return Ok(result as usize);
+        }
+    }
+}
+
+/// File sink that writes through O_DIRECT from a set of registered, page aligned
+/// buffers. Full chunks are handed to the kernel with `WRITE_FIXED` while the next
+/// chunk is being filled, so the writer thread never blocks on the disk unless all
+/// buffers are in flight.
+pub struct UringFileWriter {
+    ring: IoUring,
+    bufs: Vec<AlignedBuf>,
+    free: Vec<u16>,
+    submitted_len: Vec<usize>,
+    current: u16,
+    fill: usize,
+    offset: u64,
+    inflight: usize,
+    file: File,
+}
+
+impl UringFileWriter {
+    pub fn create<P: AsRef<Path>>(path: P, chunk_size: usize, nbufs: u16) -> std::io::Result<Self> {
+        assert!(chunk_size > 0 && chunk_size.is_multiple_of(DIRECT_IO_ALIGN));
+        assert!(nbufs >= 2);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)?;
+        let ring = IoUring::new(nbufs as u32)?;
+        let bufs: Vec<_> = (0..nbufs).map(|_| AlignedBuf::new(chunk_size)).collect();
+        let iovecs: Vec<_> = bufs
+            .iter()
+            .map(|b| libc::iovec {
+                iov_base: b.ptr as *mut libc::c_void,
+                iov_len: b.len,
+            })
+            .collect();
+        // io_uring is built against its own libc, whose iovec has the same layout
+        unsafe {
+            ring.submitter()
+                .register_buffers(std::slice::from_raw_parts(
+                    iovecs.as_ptr() as *const _,
+                    iovecs.len(),
+                ))?
+        };
+
+        Ok(Self {
+            ring,
+            bufs,
+            free: (1..nbufs).rev().collect(),
+            submitted_len: vec![0; nbufs as usize],
+            current: 0,
+            fill: 0,
+            offset: 0,
+            inflight: 0,
+            file,
+        })
+    }
+
+    fn submit_current(&mut self, len: usize) -> std::io::Result<()> {
+        let idx = self.current;
+        let write = opcode::WriteFixed::new(
+            types::Fd(self.file.as_raw_fd()),
+            self.bufs[idx as usize].ptr,
+            len as u32,
+            idx,
+        )
+        .offset(self.offset)
+        .build()
+        .user_data(idx as u64);
+        push_sqe(&mut self.ring, &write)?;
+        self.ring.submit()?;
+        self.submitted_len[idx as usize] = len;
+        self.offset += len as u64;
+        self.inflight += 1;
+        Ok(())
+    }
+
+    fn reap(&mut self, want: usize) -> std::io::Result<()> {
+        if want > 0 {
+            self.ring.submit_and_wait(want)?;
+        }
+        let mut result = Ok(());
+        for cqe in self.ring.completion() {
+            let idx = cqe.user_data() as u16;
+            self.inflight -= 1;
+            self.free.push(idx);
+            if cqe.result() < 0 {
+                result = Err(Error::from_raw_os_error(-cqe.result()));
+            } else if cqe.result() as usize != self.submitted_len[idx as usize] {
+                result = Err(Error::new(ErrorKind::WriteZero, "short O_DIRECT write"));
+            }
+        }
+        result
+    }
+
+    fn next_free(&mut self) -> std::io::Result<u16> {
+        self.reap(0)?;
+        while self.free.is_empty() {
+            self.reap(1)?;
+        }
+        Ok(self.free.pop().unwrap())
+    }
+
+    /// Waits for all pending writes and writes the unaligned tail, if any,
+    /// with O_DIRECT switched off.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.finalize()
+    }
+
+    fn finalize(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        if self.fill > 0 {
+            let fd = self.file.as_raw_fd();
+            unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_DIRECT) < 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            let tail = &self.bufs[self.current as usize].as_slice()[..self.fill];
+            self.file.write_all_at(tail, self.offset)?;
+            self.offset += self.fill as u64;
+            self.fill = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Write for UringFileWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let chunk_size = self.bufs[0].len;
+        let mut written = 0;
+        while written < data.len() {
+            let n = (chunk_size - self.fill).min(data.len() - written);
+            let fill = self.fill;
+            self.bufs[self.current as usize].as_mut_slice()[fill..fill + n]
+                .copy_from_slice(&data[written..written + n]);
+            self.fill += n;
+            written += n;
+            if self.fill == chunk_size {
+                self.submit_current(chunk_size)?;
+                self.current = self.next_free()?;
+                self.fill = 0;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Submits the aligned part of the current chunk and waits until every
+    /// write has reached the file. An unaligned remainder stays buffered.
+    fn flush(&mut self) -> std::io::Result<()> {
+        let aligned = self.fill / DIRECT_IO_ALIGN * DIRECT_IO_ALIGN;
+        if aligned > 0 {
+            let tail = self.fill - aligned;
+            let old = self.current as usize;
+            self.submit_current(aligned)?;
+            self.current = self.next_free()?;
+            let (src, dst) = (self.bufs[old].ptr, self.bufs[self.current as usize].ptr);
+            unsafe { std::ptr::copy_nonoverlapping(src.add(aligned), dst, tail) };
+            self.fill = tail;
+        }
+        while self.inflight > 0 {
+            self.reap(1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringFileWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            eprintln!("failed to finish io_uring writes: {e}");
+        }
+    }
+}
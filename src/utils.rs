@@ -1,5 +1,6 @@
-use std::{net::UdpSocket, os::fd::AsRawFd, slice::{from_raw_parts, from_raw_parts_mut}};
+use std::{net::{Ipv4Addr, UdpSocket}, os::fd::AsRawFd, slice::{from_raw_parts, from_raw_parts_mut}};
 
+use chrono::{DateTime, Utc};
 use libc::{setsockopt, socklen_t, SOL_SOCKET, SO_RCVBUF};
 
 pub fn as_u8_slice<'a, 'b, T: Sized>(x: &'a T) -> &'b [u8]
@@ -40,3 +41,155 @@ pub fn set_recv_buffer_size(socket: &UdpSocket, size: usize) -> std::io::Result<
         Err(std::io::Error::last_os_error())
     }
 }
+
+fn set_ip_option<T>(socket: &UdpSocket, name: libc::c_int, value: &T) -> std::io::Result<()> {
+    let ret = unsafe {
+        setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as socklen_t,
+        )
+    };
+
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+fn mreq_source(group: Ipv4Addr, iface: Ipv4Addr, source: Ipv4Addr) -> libc::ip_mreq_source {
+    let in_addr = |a: Ipv4Addr| libc::in_addr {
+        s_addr: u32::from(a).to_be(),
+    };
+    libc::ip_mreq_source {
+        imr_multiaddr: in_addr(group),
+        imr_interface: in_addr(iface),
+        imr_sourceaddr: in_addr(source),
+    }
+}
+
+/// Joins `group` on the interface with address `iface`, only for datagrams
+/// sent by `source` (IGMPv3 source-specific multicast).
+pub fn join_source_specific_v4(
+    socket: &UdpSocket,
+    group: Ipv4Addr,
+    iface: Ipv4Addr,
+    source: Ipv4Addr,
+) -> std::io::Result<()> {
+    let mreq = mreq_source(group, iface, source);
+    set_ip_option(socket, libc::IP_ADD_SOURCE_MEMBERSHIP, &mreq)
+}
+
+pub fn leave_source_specific_v4(
+    socket: &UdpSocket,
+    group: Ipv4Addr,
+    iface: Ipv4Addr,
+    source: Ipv4Addr,
+) -> std::io::Result<()> {
+    let mreq = mreq_source(group, iface, source);
+    set_ip_option(socket, libc::IP_DROP_SOURCE_MEMBERSHIP, &mreq)
+}
+
+/// Whether a socket bound to the wildcard address also gets the groups other
+/// sockets on the host joined for the same port, which Linux does by default.
+pub fn set_multicast_all(socket: &UdpSocket, all: bool) -> std::io::Result<()> {
+    let on = all as libc::c_int;
+    set_ip_option(socket, libc::IP_MULTICAST_ALL, &on)
+}
+
+/// IPv4 address of the interface `iface`, given by name (e.g. `eth0`) or
+/// already as an address.
+pub fn iface_addr(iface: &str) -> std::io::Result<Ipv4Addr> {
+    if let Ok(addr) = iface.parse() {
+        return Ok(addr);
+    }
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut found = None;
+    let mut p = addrs;
+    while !p.is_null() {
+        let ifa = unsafe { &*p };
+        p = ifa.ifa_next;
+        if ifa.ifa_addr.is_null() || unsafe { (*ifa.ifa_addr).sa_family } != libc::AF_INET as libc::sa_family_t {
+            continue;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) };
+        if name.to_bytes() == iface.as_bytes() {
+            let sin = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+            found = Some(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)));
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+    found.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no interface {iface} with an IPv4 address"),
+        )
+    })
+}
+
+/// Makes the kernel attach a `SO_TIMESTAMPNS` receive time to every datagram,
+/// read back with [`recv_with_timestamp`].
+pub fn enable_rx_timestamps(socket: &UdpSocket) -> std::io::Result<()> {
+    let fd = socket.as_raw_fd();
+    let on: libc::c_int = 1;
+
+    let ret = unsafe {
+        setsockopt(
+            fd,
+            SOL_SOCKET,
+            libc::SO_TIMESTAMPNS,
+            &on as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&on) as socklen_t,
+        )
+    };
+
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Receives one datagram together with its kernel receive time, which is
+/// `None` unless [`enable_rx_timestamps`] was called on the socket.
+pub fn recv_with_timestamp(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, Option<DateTime<Utc>>)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // u64 for the alignment cmsghdr needs
+    let mut control = [0_u64; 8];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    let n = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut stamp = None;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS {
+                let ts = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timespec);
+                stamp = DateTime::from_timestamp(ts.tv_sec as i64, ts.tv_nsec as u32);
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok((n as usize, stamp))
+}
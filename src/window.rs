@@ -0,0 +1,179 @@
+use std::{fmt::Display, ops::Range};
+
+use chrono::{DateTime, Utc};
+
+use crate::{payload::N_PT_PER_FRAME, time_ref::TimeReference};
+
+/// Where a block lies relative to a [`CaptureWindow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clip {
+    /// Entirely before the window, drop it.
+    Before,
+    /// These items of the block are inside the window.
+    Inside(Range<usize>),
+    /// At or past the end of the window, the capture is complete.
+    After,
+}
+
+/// Half-open range `start..end` of raw sample indices to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Default for CaptureWindow {
+    /// The whole stream.
+    fn default() -> Self {
+        Self::new(0, u64::MAX)
+    }
+}
+
+impl CaptureWindow {
+    pub fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+
+    /// Frames `pkts.start..pkts.end` of the stream, by `pkt_cnt`.
+    pub fn from_pkt_range(pkts: Range<u64>) -> Self {
+        Self::new(
+            pkts.start.saturating_mul(N_PT_PER_FRAME as u64),
+            pkts.end.saturating_mul(N_PT_PER_FRAME as u64),
+        )
+    }
+
+    /// Window starting at `start` UTC and lasting `duration`, or until the
+    /// end of the stream if no duration is given.
+    pub fn from_utc(
+        time_ref: &TimeReference,
+        start: DateTime<Utc>,
+        duration: Option<std::time::Duration>,
+    ) -> Self {
+        let start_sample = time_ref.sample_index_at(start);
+        let end = match duration {
+            Some(d) => start_sample + (d.as_secs_f64() * time_ref.samp_rate).round() as u64,
+            None => u64::MAX,
+        };
+        Self::new(start_sample, end)
+    }
+
+    /// Selects the part of a block that falls into the window. The block
+    /// starts at raw sample `first_sample`, covers `n_raw` raw samples and
+    /// holds `n_items` output items spread evenly over them.
+    pub fn clip(&self, first_sample: u64, n_raw: usize, n_items: usize) -> Clip {
+        let block_end = first_sample + n_raw as u64;
+        if first_sample >= self.end {
+            return Clip::After;
+        }
+        if block_end <= self.start || n_raw == 0 {
+            return Clip::Before;
+        }
+        let to_item = |s: u64| {
+            let off = s.clamp(first_sample, block_end) - first_sample;
+            (off as u128 * n_items as u128).div_ceil(n_raw as u128) as usize
+        };
+        Clip::Inside(to_item(self.start)..to_item(self.end))
+    }
+
+    /// True if nothing after a block ending at raw sample `block_end` is needed.
+    pub fn is_complete(&self, block_end: u64) -> bool {
+        block_end >= self.end
+    }
+}
+
+/// Raw samples of a [`CaptureWindow`] that were not received.
+#[derive(Debug, Clone, Default)]
+pub struct GapReport {
+    pub n_total: u64,
+    pub n_valid: u64,
+    /// Runs of raw sample indices known to be missing, from gap filled
+    /// frames or from jumps in the stream. Partially filled blocks only
+    /// show up in the counts.
+    pub gaps: Vec<Range<u64>>,
+    /// `n_total` at each break in the stream, see
+    /// [`FLAG_DISCONTINUITY`](crate::payload::FLAG_DISCONTINUITY). Sample
+    /// indices start over after each of them.
+    pub breaks: Vec<u64>,
+    next: Option<u64>,
+}
+
+impl GapReport {
+    pub fn new(window: &CaptureWindow) -> Self {
+        Self {
+            // without an explicit start the stream may begin anywhere
+            next: (window.start > 0).then_some(window.start),
+            ..Default::default()
+        }
+    }
+
+    /// Accounts for `n_raw` samples starting at `first_sample`, `n_valid` of them received.
+    pub fn record(&mut self, first_sample: u64, n_raw: usize, n_valid: usize) {
+        if let Some(next) = self.next
+            && first_sample > next
+        {
+            self.add_gap(next..first_sample);
+            self.n_total += first_sample - next;
+        }
+        let end = first_sample + n_raw as u64;
+        if n_valid == 0 && n_raw > 0 {
+            self.add_gap(first_sample..end);
+        }
+        self.n_total += n_raw as u64;
+        self.n_valid += n_valid as u64;
+        self.next = Some(end);
+    }
+
+    /// Counts the end of `window` that never arrived, e.g. because the
+    /// stream stopped early.
+    pub fn finish(&mut self, window: &CaptureWindow) {
+        if window.end == u64::MAX {
+            return;
+        }
+        let next = self.next.unwrap_or(window.start);
+        if next < window.end {
+            self.add_gap(next..window.end);
+            self.n_total += window.end - next;
+            self.next = Some(window.end);
+        }
+    }
+
+    /// The stream broke before the next [`record`](Self::record), so a jump
+    /// in the sample index there is not a gap.
+    pub fn mark_break(&mut self) {
+        if self.n_total > 0 {
+            self.breaks.push(self.n_total);
+        }
+        self.next = None;
+    }
+
+    pub fn n_lost(&self) -> u64 {
+        self.n_total - self.n_valid
+    }
+
+    fn add_gap(&mut self, gap: Range<u64>) {
+        match self.gaps.last_mut() {
+            Some(last) if last.end == gap.start => last.end = gap.end,
+            _ => self.gaps.push(gap),
+        }
+    }
+}
+
+impl Display for GapReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} samples lost in {} gaps",
+            self.n_lost(),
+            self.n_total,
+            self.gaps.len()
+        )?;
+        let n = N_PT_PER_FRAME as u64;
+        for gap in &self.gaps {
+            write!(f, "\n  pkt_cnt {}..{}", gap.start / n, gap.end.div_ceil(n))?;
+        }
+        for b in &self.breaks {
+            write!(f, "\n  stream broke after {b} samples")?;
+        }
+        Ok(())
+    }
+}